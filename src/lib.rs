@@ -1,3 +1,50 @@
+use image::{codecs::png::PngEncoder, EncodableLayout, ImageError};
+use ndarray::{prelude::*, Zip};
+
+#[allow(unused_imports)]
+use rayon::prelude::*;
+
+use rand::distributions::Distribution;
+use rand::distributions::Uniform;
+
+use rand::*;
+
+use derive_builder::*;
+
+mod utils;
+pub use utils::*;
+
+mod renderer;
+pub use renderer::*;
+
+mod objects;
+pub use objects::*;
+
+mod materials;
+pub use materials::*;
+
+mod textures;
+pub use textures::*;
+
+mod spectrum;
+pub use spectrum::*;
+
+mod primitive_types;
+pub use primitive_types::*;
+
+mod loaders;
+pub use loaders::*;
+
+mod scene;
+pub use scene::*;
+
+use indicatif::ProgressBar;
+
+use nalgebra::*;
+
+#[cfg(debug_assertions)]
+const NORMAL: bool = false;
+
 #[cfg(test)]
 mod tests {
     #[test]
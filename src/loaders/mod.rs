@@ -0,0 +1,4 @@
+use super::*;
+
+mod obj;
+pub use obj::*;
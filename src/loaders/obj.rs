@@ -0,0 +1,741 @@
+use super::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, std::io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Image(PathBuf, ImageError),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ObjError::Image(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// A group of faces sharing the same material, with its own compacted vertex buffers.
+#[derive(Debug, Clone)]
+pub struct ObjMesh {
+    pub name: String,
+    pub material: Option<String>,
+    pub data: Arc<MeshData>,
+}
+
+#[derive(Debug)]
+pub struct ObjScene {
+    pub meshes: Vec<ObjMesh>,
+    pub materials: HashMap<String, Material>,
+}
+
+impl ObjScene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ObjError> {
+        Self::load_with_cache(path, &mut TextureCache::default())
+    }
+
+    /// Load with textures shared through `cache`.
+    pub fn load_with_cache(
+        path: impl AsRef<Path>,
+        cache: &mut TextureCache,
+    ) -> Result<Self, ObjError> {
+        ObjParser::new(path.as_ref(), cache).parse()
+    }
+
+    /// Objects for every mesh, falling back to `default_material` when the mesh has no
+    /// (known) material.
+    pub fn objects<'a>(&'a self, default_material: &'a Material) -> Vec<Object<'a>> {
+        self.meshes
+            .iter()
+            .map(|mesh| {
+                let material = match mesh.material {
+                    Some(ref name) => self.materials.get(name).unwrap_or_else(|| {
+                        log::warn!("Material {} is not defined, using the default.", name);
+                        default_material
+                    }),
+                    None => default_material,
+                };
+                Object::Mesh(
+                    MeshBuilder::default()
+                        .data(mesh.data.clone())
+                        .material(material)
+                        .build()
+                        .unwrap(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// The subset of an `.mtl` material definition that maps onto `Material`.
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    pub kd: Vector3<f64>,
+    pub ks: Vector3<f64>,
+    pub tf: Vector3<f64>,
+    pub ke: Vector3<f64>,
+    pub ns: f64,
+    pub ni: f64,
+    pub d: f64,
+    pub illum: Option<u32>,
+    pub map_kd: Option<PathBuf>,
+    /// Height map and its `-bm` multiplier
+    pub map_bump: Option<(PathBuf, f64)>,
+    /// Tangent space normal map
+    pub norm: Option<PathBuf>,
+    /// The PBR extension: roughness, metallic, sheen, clearcoat and clearcoat roughness
+    pub pr: Option<f64>,
+    pub pm: Option<f64>,
+    pub ps: Option<f64>,
+    pub pc: Option<f64>,
+    pub pcr: Option<f64>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            kd: vector![0.8, 0.8, 0.8],
+            ks: vector![0.0, 0.0, 0.0],
+            tf: vector![1.0, 1.0, 1.0],
+            ke: vector![0.0, 0.0, 0.0],
+            ns: 0.0,
+            ni: 1.5,
+            d: 1.0,
+            illum: None,
+            map_kd: None,
+            map_bump: None,
+            norm: None,
+            pr: None,
+            pm: None,
+            ps: None,
+            pc: None,
+            pcr: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn transparent(&self) -> bool {
+        self.d < 1.0 || matches!(self.illum, Some(4 | 6 | 7 | 9))
+    }
+
+    fn pbr(&self) -> bool {
+        [self.pr, self.pm, self.ps, self.pc, self.pcr]
+            .iter()
+            .any(Option::is_some)
+    }
+
+    /// Whether `ni` ends up in the material, as glass or principled transmission.
+    /// Exporters write `Ni 0` on opaque materials, so it only has to be valid then.
+    pub fn refracts(&self) -> bool {
+        self.ke.max() <= 0.0
+            && if self.pbr() {
+                self.d < 1.0
+            } else {
+                self.transparent()
+            }
+    }
+
+    pub fn to_material(&self, cache: &mut TextureCache) -> Result<Material, ObjError> {
+        let transparent = self.transparent();
+        let pbr = self.pbr();
+        let specular = self.ks.max() > self.kd.max() || matches!(self.illum, Some(3 | 5 | 8));
+        let normal_map = match (&self.norm, &self.map_bump) {
+            (Some(path), _) => Some(NormalMap::Tangent(image_texture(cache, path, false)?)),
+            (None, Some((path, strength))) => Some(NormalMap::Bump {
+                height: image_texture(cache, path, false)?,
+                strength: *strength,
+            }),
+            (None, None) => None,
+        };
+
+        let material = if self.ke.max() > 0.0 {
+            let strength = self.ke.max();
+            Material::Emissive(
+                EmissiveBuilder::default()
+                    .color(self.ke / strength)
+                    .strength(strength)
+                    .build()
+                    .unwrap(),
+            )
+        } else if pbr {
+            let mut builder = PrincipledBuilder::default();
+            match self.map_kd {
+                Some(ref path) => builder.base_color(image_texture(cache, path, true)?),
+                None => builder.base_color(self.kd),
+            };
+            builder
+                .roughness(self.pr.unwrap_or(0.5))
+                .metallic(self.pm.unwrap_or(0.0))
+                .sheen(self.ps.unwrap_or(0.0))
+                .clearcoat(self.pc.unwrap_or(0.0))
+                .transmission(1.0 - self.d.clamp(0.0, 1.0))
+                .ir(self.ni);
+            if let Some(pcr) = self.pcr {
+                builder.clearcoat_roughness(pcr);
+            }
+            if let Some(normal_map) = normal_map {
+                builder.normal_map(normal_map);
+            }
+            Material::Principled(builder.build().unwrap())
+        } else if transparent {
+            let mut builder = GlassBuilder::default();
+            builder.ir(self.ni).albedo(self.tf);
+            if let Some(normal_map) = normal_map {
+                builder.normal_map(normal_map);
+            }
+            Material::Glass(builder.build().unwrap())
+        } else if specular {
+            // Blinn-Phong exponent to a Beckmann-like alpha, which is roughness squared
+            let roughness = (2.0 / (self.ns + 2.0)).sqrt().sqrt();
+            let mut builder = MetalBuilder::default();
+            builder.albedo(self.ks).roughness(roughness);
+            if let Some(normal_map) = normal_map {
+                builder.normal_map(normal_map);
+            }
+            Material::Metal(builder.build().unwrap())
+        } else {
+            let mut builder = DiffuseBuilder::default();
+            match self.map_kd {
+                Some(ref path) => builder.albedo(image_texture(cache, path, true)?),
+                None => builder.albedo(self.kd),
+            };
+            if let Some(normal_map) = normal_map {
+                builder.normal_map(normal_map);
+            }
+            Material::Diffuse(builder.build().unwrap())
+        };
+        Ok(material)
+    }
+}
+
+fn image_texture(
+    cache: &mut TextureCache,
+    path: &Path,
+    srgb: bool,
+) -> Result<TextureKind, ObjError> {
+    let image = cache
+        .load(path, srgb)
+        .map_err(|err| ObjError::Image(path.to_path_buf(), err))?;
+    Ok(TextureKind::Image(
+        ImageTextureBuilder::default().image(image).build().unwrap(),
+    ))
+}
+
+struct LineParser<'p> {
+    path: &'p Path,
+    line: usize,
+}
+
+impl LineParser<'_> {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn floats<const N: usize>(
+        &self,
+        args: &[&str],
+        defaults: [Option<f64>; N],
+    ) -> Result<[f64; N], ObjError> {
+        let mut values = [0.0; N];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = match (args.get(i), defaults[i]) {
+                (Some(arg), _) => arg
+                    .parse()
+                    .map_err(|_| self.error(format!("Invalid number '{}'", arg)))?,
+                (None, Some(default)) => default,
+                (None, None) => return Err(self.error(format!("Expected {} numbers", N))),
+            };
+        }
+        Ok(values)
+    }
+}
+
+/// Position, texture coordinate and normal indices of a face corner.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct Group {
+    name: String,
+    material: Option<String>,
+    faces: Vec<[Corner; 3]>,
+}
+
+struct ObjParser<'p> {
+    path: &'p Path,
+    positions: Vec<Vector3<f64>>,
+    /// `None` for normals of zero length, the faces using them are shaded flat
+    normals: Vec<Option<Vector3<f64>>>,
+    uvs: Vec<Vector2<f64>>,
+    groups: Vec<Group>,
+    materials: HashMap<String, Material>,
+    cache: &'p mut TextureCache,
+}
+
+impl<'p> ObjParser<'p> {
+    fn new(path: &'p Path, cache: &'p mut TextureCache) -> Self {
+        ObjParser {
+            path,
+            cache,
+            positions: vec![],
+            normals: vec![],
+            uvs: vec![],
+            groups: vec![Group::default()],
+            materials: HashMap::new(),
+        }
+    }
+
+    fn current_group(&mut self) -> &mut Group {
+        self.groups.last_mut().unwrap()
+    }
+
+    fn start_group(&mut self, name: Option<String>, material: Option<String>) {
+        let current = self.current_group();
+        let name = name.unwrap_or_else(|| current.name.clone());
+        let material = material.or_else(|| current.material.clone());
+        if current.faces.is_empty() {
+            current.name = name;
+            current.material = material;
+            return;
+        }
+        self.groups.push(Group {
+            name,
+            material,
+            faces: vec![],
+        });
+    }
+
+    fn parse(mut self) -> Result<ObjScene, ObjError> {
+        let source = std::fs::read_to_string(self.path)
+            .map_err(|err| ObjError::Io(self.path.to_path_buf(), err))?;
+
+        for (i, line) in source.lines().enumerate() {
+            let parser = LineParser {
+                path: self.path,
+                line: i + 1,
+            };
+            let mut tokens = line.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(keyword) if !keyword.starts_with('#') => keyword,
+                _ => continue,
+            };
+            let args: Vec<&str> = tokens.collect();
+
+            match keyword {
+                "v" => {
+                    let [x, y, z] = parser.floats(&args, [None, None, None])?;
+                    self.positions.push(vector![x, y, z]);
+                }
+                "vn" => {
+                    let [x, y, z] = parser.floats(&args, [None, None, None])?;
+                    let normal = vector![x, y, z].try_normalize(0.0);
+                    if normal.is_none() {
+                        log::warn!(
+                            "{}:{}: Ignoring a normal of zero length.",
+                            self.path.display(),
+                            parser.line
+                        );
+                    }
+                    self.normals.push(normal);
+                }
+                "vt" => {
+                    let [u, v] = parser.floats(&args, [None, Some(0.0)])?;
+                    self.uvs.push(vector![u, v]);
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(parser.error("A face needs at least 3 vertices"));
+                    }
+                    let corners = args
+                        .iter()
+                        .map(|arg| self.parse_corner(&parser, arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    // Fan triangulation, assumes convex polygons
+                    for k in 1..corners.len() - 1 {
+                        let face = [corners[0], corners[k], corners[k + 1]];
+                        self.current_group().faces.push(face);
+                    }
+                }
+                "g" | "o" => self.start_group(Some(args.join(" ")), None),
+                "usemtl" => {
+                    let name = args.join(" ");
+                    self.start_group(None, Some(name));
+                }
+                "mtllib" => {
+                    for file in args {
+                        let path = self.path.with_file_name(file);
+                        self.materials.extend(parse_mtl(&path, self.cache)?);
+                    }
+                }
+                "s" | "l" | "p" => {}
+                _ => log::warn!("Ignoring unsupported OBJ statement '{}'.", keyword),
+            }
+        }
+
+        let meshes = self
+            .groups
+            .iter()
+            .filter(|group| !group.faces.is_empty())
+            .map(|group| ObjMesh {
+                name: group.name.clone(),
+                material: group.material.clone(),
+                data: Arc::new(self.compact(group)),
+            })
+            .collect();
+
+        Ok(ObjScene {
+            meshes,
+            materials: self.materials,
+        })
+    }
+
+    fn parse_corner(&self, parser: &LineParser, arg: &str) -> Result<Corner, ObjError> {
+        let mut indices = arg.split('/');
+        let resolve = |index: Option<&str>, len: usize| -> Result<Option<usize>, ObjError> {
+            match index {
+                None | Some("") => Ok(None),
+                Some(index) => {
+                    let index: i64 = index
+                        .parse()
+                        .map_err(|_| parser.error(format!("Invalid index '{}'", index)))?;
+                    // Indices are 1-based, negative ones are relative to the end
+                    let resolved = if index < 0 {
+                        len as i64 + index
+                    } else {
+                        index - 1
+                    };
+                    if resolved < 0 || resolved >= len as i64 {
+                        return Err(parser.error(format!("Index {} is out of bounds", index)));
+                    }
+                    Ok(Some(resolved as usize))
+                }
+            }
+        };
+        let position = resolve(indices.next(), self.positions.len())?
+            .ok_or_else(|| parser.error("Missing vertex index"))?;
+        let uv = resolve(indices.next(), self.uvs.len())?;
+        let normal = resolve(indices.next(), self.normals.len())?;
+        Ok((position, uv, normal))
+    }
+
+    /// Copy only the vertices referenced by `group` into a new buffer.
+    fn compact(&self, group: &Group) -> MeshData {
+        let mut data = MeshData::default();
+        let mut position_map = HashMap::new();
+        let mut uv_map = HashMap::new();
+        let mut normal_map = HashMap::new();
+
+        for corners in &group.faces {
+            let mut face = Face {
+                positions: [0; 3],
+                normals: Some([0; 3]),
+                uvs: Some([0; 3]),
+            };
+            for (k, &(position, uv, normal)) in corners.iter().enumerate() {
+                face.positions[k] = *position_map.entry(position).or_insert_with(|| {
+                    data.positions.push(self.positions[position]);
+                    data.positions.len() - 1
+                });
+                face.uvs = match (face.uvs, uv) {
+                    (Some(mut uvs), Some(uv)) => {
+                        uvs[k] = *uv_map.entry(uv).or_insert_with(|| {
+                            data.uvs.push(self.uvs[uv]);
+                            data.uvs.len() - 1
+                        });
+                        Some(uvs)
+                    }
+                    _ => None,
+                };
+                let normal = normal.and_then(|normal| Some((normal, self.normals[normal]?)));
+                face.normals = match (face.normals, normal) {
+                    (Some(mut normals), Some((normal, value))) => {
+                        normals[k] = *normal_map.entry(normal).or_insert_with(|| {
+                            data.normals.push(value);
+                            data.normals.len() - 1
+                        });
+                        Some(normals)
+                    }
+                    _ => None,
+                };
+            }
+            data.faces.push(face);
+        }
+        data
+    }
+}
+
+fn parse_mtl(path: &Path, cache: &mut TextureCache) -> Result<HashMap<String, Material>, ObjError> {
+    let source =
+        std::fs::read_to_string(path).map_err(|err| ObjError::Io(path.to_path_buf(), err))?;
+
+    // With the line of the last `Ni`, which is only checked once the material is complete
+    let mut definitions: Vec<(String, MtlMaterial, Option<usize>)> = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let parser = LineParser { path, line: i + 1 };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            definitions.push((args.join(" "), MtlMaterial::default(), None));
+            continue;
+        }
+        let (material, ni_line) = match definitions.last_mut() {
+            Some((_, material, ni_line)) => (material, ni_line),
+            None => return Err(parser.error(format!("'{}' before newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" | "Ks" | "Tf" | "Ke" => {
+                let [r, g, b] = parser.floats(&args, [None, None, None])?;
+                let color = vector![r, g, b];
+                match keyword {
+                    "Kd" => material.kd = color,
+                    "Ks" => material.ks = color,
+                    "Ke" => material.ke = color,
+                    _ => material.tf = color,
+                }
+            }
+            "Ns" => material.ns = parser.floats(&args, [None])?[0],
+            "Ni" => {
+                material.ni = parser.floats(&args, [None])?[0];
+                *ni_line = Some(parser.line);
+            }
+            "d" => material.d = parser.floats(&args, [None])?[0],
+            "Tr" => material.d = 1.0 - parser.floats(&args, [None])?[0],
+            "illum" => {
+                let illum = args
+                    .first()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or_else(|| parser.error("Invalid illumination model"))?;
+                material.illum = Some(illum);
+            }
+            // Options like -bm are not supported, the file name is the last argument
+            "map_Kd" => match args.last() {
+                Some(file) => material.map_kd = Some(path.with_file_name(file)),
+                None => return Err(parser.error("Missing texture file name")),
+            },
+            "map_Bump" | "map_bump" | "bump" => {
+                let file = match args.last() {
+                    Some(file) => path.with_file_name(file),
+                    None => return Err(parser.error("Missing texture file name")),
+                };
+                let strength = match args.iter().position(|arg| *arg == "-bm") {
+                    Some(i) => parser.floats(&args[i + 1..], [None])?[0],
+                    None => 1.0,
+                };
+                material.map_bump = Some((file, strength));
+            }
+            "norm" => match args.last() {
+                Some(file) => material.norm = Some(path.with_file_name(file)),
+                None => return Err(parser.error("Missing texture file name")),
+            },
+            "Pr" | "Pm" | "Ps" | "Pc" | "Pcr" => {
+                let value = Some(parser.floats(&args, [None])?[0]);
+                match keyword {
+                    "Pr" => material.pr = value,
+                    "Pm" => material.pm = value,
+                    "Ps" => material.ps = value,
+                    "Pc" => material.pc = value,
+                    _ => material.pcr = value,
+                }
+            }
+            "Ka" => {}
+            _ => log::warn!("Ignoring unsupported MTL statement '{}'.", keyword),
+        }
+    }
+
+    definitions
+        .into_iter()
+        .map(|(name, material, ni_line)| {
+            if let Some(line) = ni_line {
+                if material.refracts() && !(material.ni > 0.0 && material.ni.is_finite()) {
+                    let parser = LineParser { path, line };
+                    return Err(parser.error("The index of refraction must be positive"));
+                }
+            }
+            Ok((name, material.to_material(cache)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_mtl_source(name: &str, source: &str) -> Result<HashMap<String, Material>, ObjError> {
+        let path = std::env::temp_dir().join(format!("rustyray-{}.mtl", name));
+        std::fs::write(&path, source).unwrap();
+        let result = parse_mtl(&path, &mut TextureCache::default());
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn parse_obj_source(name: &str, source: &str) -> Result<ObjScene, ObjError> {
+        let path = std::env::temp_dir().join(format!("rustyray-{}.obj", name));
+        std::fs::write(&path, source).unwrap();
+        let result = ObjScene::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                          vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n";
+
+    fn positions(mesh: &ObjMesh) -> Vec<[usize; 3]> {
+        mesh.data.faces.iter().map(|face| face.positions).collect()
+    }
+
+    #[test]
+    fn polygons_are_triangulated_as_fans() {
+        let source = "v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n";
+        let scene = parse_obj_source("fan", source).unwrap();
+        assert_eq!(
+            positions(&scene.meshes[0]),
+            [[0, 1, 2], [0, 2, 3], [0, 3, 4]]
+        );
+        assert!(matches!(
+            parse_obj_source("fan", "v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            Err(ObjError::Parse { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn negative_indices_count_from_the_last_vertex() {
+        let source = format!("{}f -4 -3 -2\nv 5 5 5\nf 1 -2 -1\n", SQUARE);
+        let scene = parse_obj_source("negative", &source).unwrap();
+        let data = &scene.meshes[0].data;
+        assert_eq!(positions(&scene.meshes[0]), [[0, 1, 2], [0, 3, 4]]);
+        assert_eq!(data.positions[3], vector![0.0, 1.0, 0.0]);
+        assert_eq!(data.positions[4], vector![5.0, 5.0, 5.0]);
+
+        for face in ["f 1 2 5", "f 0 1 2", "f -5 1 2", "f 1/5 2/1 3/1"] {
+            let source = format!("{}{}\n", SQUARE, face);
+            match parse_obj_source("negative", &source) {
+                Err(ObjError::Parse {
+                    line: 10, message, ..
+                }) => {
+                    assert!(message.ends_with("is out of bounds"), "{}", message)
+                }
+                other => panic!("{} gave {:?}", face, other),
+            }
+        }
+    }
+
+    #[test]
+    fn corners_with_texture_coordinates_and_normals() {
+        let faces = [
+            ("f 1/1/1 2/2/1 3/3/1", true, true),
+            ("f 1//1 2//1 3//1", false, true),
+            ("f 1/1 2/2 3/3", true, false),
+            ("f 1 2 3", false, false),
+        ];
+        for (face, uvs, normals) in faces {
+            let source = format!("{}{}\n", SQUARE, face);
+            let scene = parse_obj_source("corners", &source).unwrap();
+            let data = &scene.meshes[0].data;
+            assert_eq!(data.faces[0].uvs.is_some(), uvs, "{}", face);
+            assert_eq!(data.faces[0].normals.is_some(), normals, "{}", face);
+            assert_eq!(data.uvs.len(), if uvs { 3 } else { 0 });
+            assert_eq!(data.normals.len(), if normals { 1 } else { 0 });
+        }
+        // A face mixing corners with and without normals is shaded flat
+        let source = format!("{}f 1//1 2 3//1\n", SQUARE);
+        let scene = parse_obj_source("corners", &source).unwrap();
+        assert!(scene.meshes[0].data.faces[0].normals.is_none());
+    }
+
+    #[test]
+    fn zero_normals_are_ignored() {
+        let source = format!("{}vn 0 0 0\nf 1//2 2//2 3//2\nf 1//1 3//1 4//1\n", SQUARE);
+        let scene = parse_obj_source("zero-normal", &source).unwrap();
+        let data = &scene.meshes[0].data;
+        assert!(data.faces[0].normals.is_none());
+        assert_eq!(data.faces[1].normals, Some([0, 0, 0]));
+        assert_eq!(data.normals, [vector![0.0, 0.0, 1.0]]);
+    }
+
+    #[test]
+    fn groups_and_materials_split_meshes() {
+        let source = format!(
+            "{}f 1 2 3\ng lid\nf 1 3 4\nusemtl red\nf 2 3 4\nusemtl blue\ng base\nf 1 2 4\n",
+            SQUARE
+        );
+        let scene = parse_obj_source("groups", &source).unwrap();
+        let meshes: Vec<_> = scene
+            .meshes
+            .iter()
+            .map(|mesh| (mesh.name.as_str(), mesh.material.as_deref()))
+            .collect();
+        assert_eq!(
+            meshes,
+            [
+                ("", None),
+                ("lid", None),
+                ("lid", Some("red")),
+                ("base", Some("blue"))
+            ]
+        );
+        // Every mesh only keeps the vertices it uses
+        assert_eq!(positions(&scene.meshes[2]), [[0, 1, 2]]);
+        assert_eq!(scene.meshes[2].data.positions[0], vector![1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn index_of_refraction_must_be_positive() {
+        for ni in ["0", "-1.5", "nan"] {
+            let source = format!("newmtl glass\nKd 1 1 1\nNi {}\nd 0\nPr 0.2\n", ni);
+            match parse_mtl_source("ni", &source) {
+                Err(ObjError::Parse { line, message, .. }) => {
+                    assert_eq!(line, 3);
+                    assert_eq!(message, "The index of refraction must be positive");
+                }
+                other => panic!("Ni {} gave {:?}", ni, other),
+            }
+        }
+        let materials =
+            parse_mtl_source("ni-valid", "newmtl glass\nNi 1.5\nd 0\nPr 0.2\n").unwrap();
+        match &materials["glass"] {
+            Material::Principled(principled) => assert!(principled.transmits()),
+            other => panic!("expected a principled material, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn opaque_materials_ignore_index_of_refraction() {
+        let source = "newmtl wall\nKd 0.8 0.8 0.8\nNi 0.000000\nd 1.0\n\
+                      newmtl plastic\nNi 0\nPr 0.4\n\
+                      newmtl glass\nNi 0\nd 0\n";
+        match parse_mtl_source("ni-opaque", source) {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 9),
+            other => panic!("expected the glass to be rejected, got {:?}", other),
+        }
+        let source = "newmtl wall\nKd 0.8 0.8 0.8\nNi 0.000000\nd 1.0\n\
+                      newmtl plastic\nNi 0\nPr 0.4\n";
+        let materials = parse_mtl_source("ni-opaque", source).unwrap();
+        assert!(matches!(materials["wall"], Material::Diffuse(_)));
+        assert!(matches!(materials["plastic"], Material::Principled(_)));
+    }
+}
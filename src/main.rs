@@ -1,84 +1,286 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
 
-use image::{codecs::png::PngEncoder, EncodableLayout, ImageError};
-use ndarray::{prelude::*, Zip};
+use clap::{Args, Parser, Subcommand};
 
-#[allow(unused_imports)]
-use rayon::prelude::*;
-
-use rand::distributions::Distribution;
 use rand::distributions::Uniform;
 
 use rand::*;
 
-use derive_builder::*;
+use nalgebra::*;
 
-mod utils;
-use utils::*;
+use rustyray::*;
 
-mod renderer;
-use renderer::*;
+#[derive(Parser)]
+#[clap(version, about = "A simple multi-threaded raytracer")]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
 
-mod objects;
-use objects::*;
+#[derive(Subcommand)]
+enum Command {
+    /// Render a scene file, or the built-in cover scene if none is given
+    Render(RenderArgs),
+    /// Print a summary of a scene file
+    Info { scene: PathBuf },
+    /// Render the built-in cover scene and report the throughput
+    Bench(BenchArgs),
+}
 
-mod materials;
-use materials::*;
+#[derive(Args)]
+struct RenderArgs {
+    scene: Option<PathBuf>,
+    /// Where to save the PNG
+    #[clap(short, long, default_value = "sample_renders/test.png")]
+    output: PathBuf,
+    #[clap(flatten)]
+    settings: Settings,
+}
 
-mod primitive_types;
-use primitive_types::*;
+#[derive(Args)]
+struct BenchArgs {
+    /// Number of times to render the scene
+    #[clap(long, default_value_t = 3)]
+    runs: u32,
+    #[clap(flatten)]
+    settings: Settings,
+}
 
-use indicatif::ProgressBar;
+/// Overrides for the settings in the scene.
+#[derive(Args)]
+struct Settings {
+    /// Samples per pixel
+    #[clap(long)]
+    spp: Option<u32>,
+    /// Maximum number of bounces
+    #[clap(long)]
+    depth: Option<u32>,
+    /// Number of worker threads
+    #[clap(long)]
+    threads: Option<u32>,
+    /// Image width in pixels
+    #[clap(long)]
+    width: Option<u32>,
+    /// Image height in pixels
+    #[clap(long)]
+    height: Option<u32>,
+    /// Seed for reproducible renders
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Trace sampled wavelengths instead of RGB
+    #[clap(long)]
+    spectral: bool,
+}
 
-use nalgebra::*;
+impl Settings {
+    fn apply(&self, builder: &mut RendererBuilder, canvas: &mut CanvasBuilder) {
+        if let Some(spp) = self.spp {
+            builder.samples(spp);
+        }
+        if let Some(depth) = self.depth {
+            builder.max_depth(depth);
+        }
+        if let Some(threads) = self.threads {
+            builder.thread_count(threads);
+        }
+        if let Some(seed) = self.seed {
+            builder.seed(seed);
+        }
+        if self.spectral {
+            builder.spectral(true);
+        }
+        if let Some(width) = self.width {
+            canvas.width(width);
+        }
+        if let Some(height) = self.height {
+            canvas.height(height);
+        }
+    }
+}
 
-#[cfg(debug_assertions)]
-const NORMAL: bool = false;
-
-fn main() {
-    // World
-    let materials = build_materials();
-    let objects = build_objects(&materials);
-
-    let mut renderer = RendererBuilder::default()
-        .thread_count(32)
-        .scene_objects(&objects[..])
-        .camera(
-            CameraBuilder::default()
-                .origin(vector![13.0, 2.0, 3.0])
-                .vfov(20.0)
-                .focus_dist(10.0)
-                .look_at(vector![0.0, 0.0, 0.0])
-                .aperture(0.1)
-                .build()
-                .unwrap(),
-        )
-        .build()
-        .unwrap();
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Render(args) => render(args),
+        Command::Info { scene } => info(scene),
+        Command::Bench(args) => bench(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn render(args: RenderArgs) -> Result<(), Box<dyn Error>> {
+    let scene;
+    let materials;
+    let objects;
+    let mut renderer;
+    match args.scene {
+        Some(path) => {
+            scene = Scene::load(path)?;
+            objects = scene.objects();
+            let mut canvas = scene.canvas.clone();
+            let mut builder = scene.renderer(&objects[..]);
+            args.settings.apply(&mut builder, &mut canvas);
+            renderer = builder.canvas(canvas.build()?).build()?;
+        }
+        None => {
+            let mut rng = cover_rng(args.settings.seed);
+            materials = build_materials(&mut rng);
+            objects = build_objects(&materials, &mut rng);
+            renderer = cover_renderer(&objects, &args.settings)?;
+        }
+    }
 
     renderer.render();
-    renderer.save_render("sample_renders/test.png");
+    renderer
+        .save_render(&args.output)
+        .map_err(|err| format!("{}: {}", args.output.display(), err))?;
+    Ok(())
+}
+
+fn info(path: PathBuf) -> Result<(), Box<dyn Error>> {
+    let scene = Scene::load(&path)?;
+    let objects = scene.objects();
+
+    let (mut spheres, mut triangles, mut meshes, mut media) = (0, 0, 0, 0);
+    for object in &objects {
+        match object {
+            Object::Sphere(_) => spheres += 1,
+            Object::Triangle(_) => triangles += 1,
+            Object::Mesh(mesh) => {
+                meshes += 1;
+                triangles += mesh.data.faces.len();
+            }
+            Object::Medium(_) | Object::Volume(_) => media += 1,
+        }
+    }
+    let bounds = objects.iter().fold(Aabb::empty(), |acc, obj| {
+        acc.surrounding(&obj.bounding_box())
+    });
+
+    println!("Scene: {}", path.display());
+    println!("Materials: {}", scene.materials.len());
+    println!(
+        "Objects: {} ({} spheres, {} meshes, {} triangles, {} media)",
+        objects.len(),
+        spheres,
+        meshes,
+        triangles,
+        media
+    );
+    let camera = scene.camera.build()?;
+    println!(
+        "Camera: [{:.3}, {:.3}, {:.3}] looking at [{:.3}, {:.3}, {:.3}], {}° field of view",
+        camera.origin[0],
+        camera.origin[1],
+        camera.origin[2],
+        camera.look_at[0],
+        camera.look_at[1],
+        camera.look_at[2],
+        camera.vfov
+    );
+    if objects.is_empty() {
+        println!("Bounds: empty");
+    } else {
+        println!(
+            "Bounds: [{:.3}, {:.3}, {:.3}] to [{:.3}, {:.3}, {:.3}]",
+            bounds.minimum[0],
+            bounds.minimum[1],
+            bounds.minimum[2],
+            bounds.maximum[0],
+            bounds.maximum[1],
+            bounds.maximum[2]
+        );
+    }
+    Ok(())
+}
+
+fn bench(args: BenchArgs) -> Result<(), Box<dyn Error>> {
+    let settings = Settings {
+        spp: Some(args.settings.spp.unwrap_or(16)),
+        width: Some(args.settings.width.unwrap_or(320)),
+        height: Some(args.settings.height.unwrap_or(180)),
+        seed: Some(args.settings.seed.unwrap_or(0)),
+        ..args.settings
+    };
+    let mut rng = cover_rng(settings.seed);
+    let materials = build_materials(&mut rng);
+    let objects = build_objects(&materials, &mut rng);
+
+    let paths = settings.spp.unwrap() as f64
+        * settings.width.unwrap() as f64
+        * settings.height.unwrap() as f64;
+    let mut best = f64::INFINITY;
+    for run in 1..=args.runs {
+        let mut renderer = cover_renderer(&objects, &settings)?;
+        let start = Instant::now();
+        renderer.render();
+        let elapsed = start.elapsed().as_secs_f64();
+        best = best.min(elapsed);
+        println!(
+            "Run {}: {:.3}s ({:.0} paths/s)",
+            run,
+            elapsed,
+            paths / elapsed
+        );
+    }
+    println!("Best: {:.3}s ({:.0} paths/s)", best, paths / best);
+    Ok(())
+}
+
+fn cover_rng(seed: Option<u64>) -> rngs::StdRng {
+    match seed {
+        Some(seed) => rngs::StdRng::seed_from_u64(seed),
+        None => rngs::StdRng::from_entropy(),
+    }
+}
+
+fn cover_renderer<'a>(
+    objects: &'a [Object<'a>],
+    settings: &Settings,
+) -> Result<Renderer<'a>, Box<dyn Error>> {
+    let mut builder = RendererBuilder::default();
+    builder.thread_count(32).scene_objects(objects).camera(
+        CameraBuilder::default()
+            .origin(vector![13.0, 2.0, 3.0])
+            .vfov(20.0)
+            .focus_dist(10.0)
+            .look_at(vector![0.0, 0.0, 0.0])
+            .aperture(0.1)
+            .build()?,
+    );
+    let mut canvas = CanvasBuilder::default();
+    settings.apply(&mut builder, &mut canvas);
+    Ok(builder.canvas(canvas.build()?).build()?)
 }
 
-fn build_materials() -> HashMap<String, Material> {
+fn build_materials(rng: &mut impl Rng) -> HashMap<String, Material> {
     let mut materials = HashMap::new();
 
-    let mut rng = thread_rng();
     for i in 0..484 {
         let choose_mat = rng.gen::<f64>();
         if choose_mat < 0.8 {
-            let albedo =
-                Vector3::from_distribution(&Uniform::new(0.0, 1.0), &mut rng).map(|x| x * x);
+            let albedo = Vector3::from_distribution(&Uniform::new(0.0, 1.0), rng).map(|x| x * x);
             let material =
                 Material::Diffuse(DiffuseBuilder::default().albedo(albedo).build().unwrap());
             materials.insert(format!("{}", i), material);
         } else if choose_mat < 0.95 {
-            let albedo = Vector3::from_distribution(&Uniform::new(0.5, 1.0), &mut rng);
-            let fuzz = rng.gen::<f64>() / 2.0;
+            let albedo = Vector3::from_distribution(&Uniform::new(0.5, 1.0), rng);
+            let roughness = rng.gen::<f64>() / 2.0;
             let material = Material::Metal(
                 MetalBuilder::default()
                     .albedo(albedo)
-                    .fuzziness(fuzz)
+                    .roughness(roughness)
                     .build()
                     .unwrap(),
             );
@@ -116,8 +318,10 @@ fn build_materials() -> HashMap<String, Material> {
     materials
 }
 
-fn build_objects(materials: &HashMap<String, Material>) -> Vec<Object> {
-    let mut rng = thread_rng();
+fn build_objects<'a>(
+    materials: &'a HashMap<String, Material>,
+    rng: &mut impl Rng,
+) -> Vec<Object<'a>> {
     let mut objects = vec![];
     let mut counter = 0;
 
@@ -0,0 +1,53 @@
+use super::*;
+
+/// An outgoing direction picked by `Bsdf::sample`.
+#[derive(Debug, Clone)]
+pub struct BsdfSample {
+    pub direction: Vector3<f64>,
+    /// The BSDF times the cosine term divided by the pdf, i.e. the path throughput
+    pub weight: Vector3<f64>,
+    /// Solid angle density, or the discrete probability for specular samples
+    pub pdf: f64,
+    pub is_specular: bool,
+    /// Carried by the scattered ray, usually the incoming ray's
+    pub wavelengths: Option<Wavelengths>,
+}
+
+/// The defaults describe a perfectly specular material, which can't be evaluated for an
+/// arbitrary direction.
+pub trait Bsdf {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample>;
+
+    /// The BSDF times the cosine term, for light leaving towards `direction`.
+    fn eval(&self, _ray: &Ray, _hit_rec: &HitRecord, _direction: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::zeros()
+    }
+
+    /// The solid angle density with which `sample` picks `direction`.
+    fn pdf(&self, _ray: &Ray, _hit_rec: &HitRecord, _direction: &Vector3<f64>) -> f64 {
+        0.0
+    }
+
+    /// Whether every sample is specular, in which case lights are not sampled explicitly.
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+impl<T: Bsdf> Bsdf for &T {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        (*self).sample(ray, hit_rec)
+    }
+
+    fn eval(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        (*self).eval(ray, hit_rec, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        (*self).pdf(ray, hit_rec, direction)
+    }
+
+    fn is_specular(&self) -> bool {
+        (*self).is_specular()
+    }
+}
@@ -0,0 +1,94 @@
+use super::*;
+
+/// A wavelength dependent index of refraction.
+#[derive(Debug, Clone)]
+pub enum Dispersion {
+    /// `a + b / λ²` with λ in micrometers
+    Cauchy { a: f64, b: f64 },
+    /// `n² = 1 + Σ bᵢ λ² / (λ² - cᵢ)` with λ in micrometers
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub const PRESETS: [&'static str; 3] = ["bk7", "fused_silica", "diamond"];
+
+    /// The Fraunhofer d line, where catalogues quote the index of refraction
+    pub const REFERENCE_WAVELENGTH: f64 = 587.6;
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let (b, c) = match name {
+            "bk7" => (
+                [1.039_612_12, 0.231_792_344, 1.010_469_45],
+                [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+            ),
+            "fused_silica" => (
+                [0.696_166_3, 0.407_942_6, 0.897_479_4],
+                [0.004_679_148, 0.013_512_063, 97.934_003],
+            ),
+            "diamond" => ([0.3306, 4.3356, 0.0], [0.030_625, 0.011_236, 0.0]),
+            _ => return None,
+        };
+        Some(Dispersion::Sellmeier { b, c })
+    }
+
+    /// Index of refraction at `wavelength` in nanometers.
+    pub fn ir(&self, wavelength: f64) -> f64 {
+        let micrometers = wavelength / 1000.0;
+        let squared = micrometers.powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / squared,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * squared / (squared - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    /// Whether the index of refraction is finite and positive at every wavelength that can
+    /// be traced, not only at `REFERENCE_WAVELENGTH`.
+    pub fn is_valid(&self) -> bool {
+        if let Dispersion::Sellmeier { b, c } = self {
+            // A pole in the visible range would be stepped over by the samples below
+            let range = (WAVELENGTH_MIN / 1000.0).powi(2)..=(WAVELENGTH_MAX / 1000.0).powi(2);
+            if (0..3).any(|i| b[i] != 0.0 && range.contains(&c[i])) {
+                return false;
+            }
+        }
+        let steps = (WAVELENGTH_MAX - WAVELENGTH_MIN) as usize;
+        (0..=steps).all(|step| {
+            let ir = self.ir(WAVELENGTH_MIN + step as f64);
+            ir.is_finite() && ir > 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_valid() {
+        for name in Dispersion::PRESETS {
+            let dispersion = Dispersion::preset(name).unwrap();
+            assert!(dispersion.is_valid(), "{}", name);
+            let blue = dispersion.ir(450.0);
+            let red = dispersion.ir(650.0);
+            assert!(blue > red && red > 1.0, "{}", name);
+        }
+    }
+
+    #[test]
+    fn invalid_away_from_the_reference() {
+        // Fine at 587.6 nm, negative in the blue
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: -0.4 };
+        assert!(cauchy.ir(Dispersion::REFERENCE_WAVELENGTH) > 0.0);
+        assert!(!cauchy.is_valid());
+        // A resonance at 500 nm
+        let sellmeier = Dispersion::Sellmeier {
+            b: [1.0, 0.0, 0.0],
+            c: [0.25, 0.0, 0.0],
+        };
+        assert!(sellmeier.ir(Dispersion::REFERENCE_WAVELENGTH).is_finite());
+        assert!(!sellmeier.is_valid());
+    }
+}
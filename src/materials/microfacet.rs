@@ -0,0 +1,189 @@
+use super::*;
+
+/// The GGX (Trowbridge-Reitz) distribution of microfacet normals, in a local frame with the
+/// macro surface normal along +Z.
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Ggx {
+    /// Below this the surface is treated as a perfect mirror.
+    pub const SPECULAR_ALPHA: f64 = 1e-3;
+
+    /// `roughness` is perceptual (alpha is its square), `anisotropy` in [0, 1) stretches the
+    /// highlight along the tangent.
+    pub fn new(roughness: f64, anisotropy: f64) -> Self {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 0.99)).sqrt();
+        Ggx {
+            alpha_x: alpha / aspect,
+            alpha_y: alpha * aspect,
+        }
+    }
+
+    pub fn is_specular(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < Self::SPECULAR_ALPHA
+    }
+
+    /// Density of microfacet normals.
+    pub fn d(&self, half: &Vector3<f64>) -> f64 {
+        if half[2] <= 0.0 {
+            return 0.0;
+        }
+        let e =
+            (half[0] / self.alpha_x).powi(2) + (half[1] / self.alpha_y).powi(2) + half[2].powi(2);
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    fn lambda(&self, w: &Vector3<f64>) -> f64 {
+        if w[2] == 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = ((self.alpha_x * w[0]).powi(2) + (self.alpha_y * w[1]).powi(2)) / w[2].powi(2);
+        0.5 * (-1.0 + (1.0 + tan2).sqrt())
+    }
+
+    /// Masking for one direction.
+    pub fn g1(&self, w: &Vector3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height correlated masking-shadowing.
+    pub fn g(&self, wo: &Vector3<f64>, wi: &Vector3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Sample a microfacet normal visible from `wo`, which must be above the surface.
+    pub fn sample_visible(&self, wo: &Vector3<f64>) -> Vector3<f64> {
+        let mut rng = render_rng();
+        let (u1, u2) = (rng.gen::<f64>(), rng.gen::<f64>());
+        // Heitz 2018, sample the projected hemisphere of the stretched view direction
+        let vh = vector![self.alpha_x * wo[0], self.alpha_y * wo[1], wo[2]].normalize();
+        let len_sq = vh[0].powi(2) + vh[1].powi(2);
+        let t1 = if len_sq > 0.0 {
+            vector![-vh[1], vh[0], 0.0] / len_sq.sqrt()
+        } else {
+            vector![1.0, 0.0, 0.0]
+        };
+        let t2 = vh.cross(&t1);
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh[2]);
+        let p2 = (1.0 - s) * (1.0 - p1.powi(2)).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1.powi(2) - p2.powi(2)).max(0.0).sqrt() * vh;
+        vector![self.alpha_x * nh[0], self.alpha_y * nh[1], nh[2].max(1e-6)].normalize()
+    }
+
+    /// Density of `sample_visible` picking `half`.
+    pub fn pdf_visible(&self, wo: &Vector3<f64>, half: &Vector3<f64>) -> f64 {
+        if wo[2] <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(half).max(0.0) * self.d(half) / wo[2]
+    }
+}
+
+/// Local coordinates of `w` in the tangent frame of `hit_rec`, with the normal along +Z.
+pub fn to_local(hit_rec: &HitRecord, w: &Vector3<f64>) -> Vector3<f64> {
+    let (tangent, bitangent) = hit_rec.tangent_frame();
+    vector![w.dot(&tangent), w.dot(&bitangent), w.dot(&hit_rec.normal)]
+}
+
+pub fn from_local(hit_rec: &HitRecord, w: &Vector3<f64>) -> Vector3<f64> {
+    let (tangent, bitangent) = hit_rec.tangent_frame();
+    w[0] * tangent + w[1] * bitangent + w[2] * hit_rec.normal
+}
+
+/// Schlick's approximation with the reflectance at normal incidence `f0`.
+pub fn fresnel_schlick(f0: &Vector3<f64>, cos_theta: f64) -> Vector3<f64> {
+    let weight = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 + (Vector3::repeat(1.0) - f0) * weight
+}
+
+/// Exact Fresnel reflectance of a dielectric interface, `eta` is the ratio of the index of
+/// refraction on the transmitted side to the incident side.
+pub fn fresnel_dielectric(cos_theta: f64, eta: f64) -> f64 {
+    let cos_i = cos_theta.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i.powi(2)) / eta.powi(2);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (parallel.powi(2) + perpendicular.powi(2))
+}
+
+/// Exact Fresnel reflectance of a conductor with complex index of refraction `eta + ik`.
+pub fn fresnel_conductor(cos_theta: f64, eta: &Vector3<f64>, k: &Vector3<f64>) -> Vector3<f64> {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    Vector3::from_fn(|i, _| {
+        let (eta, k) = (eta[i], k[i]);
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2b2 + cos2;
+        let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos2.sqrt() * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Midpoint rule over the upper hemisphere.
+    fn integrate(f: impl Fn(&Vector3<f64>) -> f64) -> f64 {
+        let (n_cos, n_phi) = (500, 1000);
+        let mut sum = 0.0;
+        for i in 0..n_cos {
+            let cos_theta = (i as f64 + 0.5) / n_cos as f64;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            for j in 0..n_phi {
+                let phi = 2.0 * PI * (j as f64 + 0.5) / n_phi as f64;
+                sum += f(&vector![
+                    sin_theta * phi.cos(),
+                    sin_theta * phi.sin(),
+                    cos_theta
+                ]);
+            }
+        }
+        sum * 2.0 * PI / (n_cos * n_phi) as f64
+    }
+
+    #[test]
+    fn projected_normals_cover_the_surface() {
+        for ggx in [Ggx::new(0.7, 0.0), Ggx::new(0.6, 0.5)] {
+            let area = integrate(|half| ggx.d(half) * half[2]);
+            assert!((area - 1.0).abs() < 1e-3, "{:?} {}", ggx, area);
+        }
+    }
+
+    #[test]
+    fn visible_normals_pdf_is_normalized() {
+        let ggx = Ggx::new(0.6, 0.5);
+        let wo = vector![0.6, -0.3, 0.5].normalize();
+        let total = integrate(|half| ggx.pdf_visible(&wo, half));
+        assert!((total - 1.0).abs() < 1e-3, "{}", total);
+    }
+
+    #[test]
+    fn sampled_normals_are_visible() {
+        let ggx = Ggx::new(0.5, 0.3);
+        let wo = vector![-0.8, 0.1, 0.2].normalize();
+        seed_render_rng(3);
+        for _ in 0..1000 {
+            let half = ggx.sample_visible(&wo);
+            assert!((half.norm() - 1.0).abs() < 1e-12);
+            assert!(half[2] > 0.0 && wo.dot(&half) >= 0.0);
+        }
+    }
+}
@@ -1,67 +1,322 @@
 use super::*;
 use std::cmp::min_by;
+use std::f64::consts::PI;
+
+mod bsdf;
+pub use bsdf::*;
+
+mod dispersion;
+pub use dispersion::*;
+
+mod microfacet;
+pub use microfacet::*;
+
+mod normal_map;
+pub use normal_map::*;
+
+mod principled;
+pub use principled::*;
+
+mod subsurface;
+pub use subsurface::*;
+
+mod thin_film;
+pub use thin_film::*;
 
 #[derive(Debug)]
 pub enum Material {
     Diffuse(Diffuse),
     Metal(Metal),
     Glass(Glass),
+    Principled(Principled),
+    Subsurface(Subsurface),
+    Isotropic(Isotropic),
+    HenyeyGreenstein(HenyeyGreenstein),
+    Emissive(Emissive),
     None,
 }
 
-impl Scatter for Material {
-    fn scatter(&self, ray: &Ray, hit_rec: &HitRecord) -> (Option<Ray>, &Vector3<f64>) {
+impl Bsdf for Material {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
         match self {
-            Material::Diffuse(diffuse) => diffuse.scatter(ray, hit_rec),
-            Material::Metal(metal) => metal.scatter(ray, hit_rec),
-            Material::Glass(glass) => glass.scatter(ray, hit_rec),
-            Material::None => unreachable!("Should not call scatter on None material!"),
+            Material::Diffuse(diffuse) => diffuse.sample(ray, hit_rec),
+            Material::Metal(metal) => metal.sample(ray, hit_rec),
+            Material::Glass(glass) => glass.sample(ray, hit_rec),
+            Material::Principled(principled) => principled.sample(ray, hit_rec),
+            Material::Subsurface(subsurface) => subsurface.sample(ray, hit_rec),
+            Material::Isotropic(isotropic) => isotropic.sample(ray, hit_rec),
+            Material::HenyeyGreenstein(phase) => phase.sample(ray, hit_rec),
+            Material::Emissive(emissive) => emissive.sample(ray, hit_rec),
+            Material::None => unreachable!("Should not call sample on None material!"),
+        }
+    }
+
+    fn eval(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        match self {
+            Material::Diffuse(diffuse) => diffuse.eval(ray, hit_rec, direction),
+            Material::Metal(metal) => metal.eval(ray, hit_rec, direction),
+            Material::Glass(glass) => glass.eval(ray, hit_rec, direction),
+            Material::Principled(principled) => principled.eval(ray, hit_rec, direction),
+            Material::Subsurface(subsurface) => subsurface.eval(ray, hit_rec, direction),
+            Material::Isotropic(isotropic) => isotropic.eval(ray, hit_rec, direction),
+            Material::HenyeyGreenstein(phase) => phase.eval(ray, hit_rec, direction),
+            Material::Emissive(emissive) => emissive.eval(ray, hit_rec, direction),
+            Material::None => unreachable!("Should not call eval on None material!"),
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        match self {
+            Material::Diffuse(diffuse) => diffuse.pdf(ray, hit_rec, direction),
+            Material::Metal(metal) => metal.pdf(ray, hit_rec, direction),
+            Material::Glass(glass) => glass.pdf(ray, hit_rec, direction),
+            Material::Principled(principled) => principled.pdf(ray, hit_rec, direction),
+            Material::Subsurface(subsurface) => subsurface.pdf(ray, hit_rec, direction),
+            Material::Isotropic(isotropic) => isotropic.pdf(ray, hit_rec, direction),
+            Material::HenyeyGreenstein(phase) => phase.pdf(ray, hit_rec, direction),
+            Material::Emissive(emissive) => emissive.pdf(ray, hit_rec, direction),
+            Material::None => unreachable!("Should not call pdf on None material!"),
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        match self {
+            Material::Diffuse(diffuse) => diffuse.is_specular(),
+            Material::Metal(metal) => metal.is_specular(),
+            Material::Glass(glass) => glass.is_specular(),
+            Material::Principled(principled) => principled.is_specular(),
+            Material::Subsurface(subsurface) => subsurface.is_specular(),
+            Material::Isotropic(isotropic) => isotropic.is_specular(),
+            Material::HenyeyGreenstein(phase) => phase.is_specular(),
+            Material::Emissive(emissive) => emissive.is_specular(),
+            Material::None => unreachable!("Should not call is_specular on None material!"),
+        }
+    }
+}
+
+impl Material {
+    /// The fraction of light surviving a segment of `distance` that ended at `hit_rec`,
+    /// which is inside the object if it hit a back face.
+    pub fn transmittance(&self, hit_rec: &HitRecord, distance: f64) -> Vector3<f64> {
+        match self {
+            Material::Glass(glass) if !hit_rec.front_face => {
+                glass.absorption.map(|sigma| (-sigma * distance).exp())
+            }
+            _ => vector![1.0, 1.0, 1.0],
+        }
+    }
+
+    /// Whether light can pass through the surface into the object.
+    pub fn transmits(&self) -> bool {
+        match self {
+            Material::Glass(_) => true,
+            Material::Principled(principled) => principled.transmits(),
+            _ => false,
+        }
+    }
+
+    pub fn normal_map(&self) -> Option<&NormalMap> {
+        match self {
+            Material::Diffuse(diffuse) => diffuse.normal_map.as_ref(),
+            Material::Metal(metal) => metal.normal_map.as_ref(),
+            Material::Glass(glass) => glass.normal_map.as_ref(),
+            Material::Principled(principled) => principled.normal_map(),
+            Material::Subsurface(_)
+            | Material::Isotropic(_)
+            | Material::HenyeyGreenstein(_)
+            | Material::Emissive(_)
+            | Material::None => None,
+        }
+    }
+}
+
+impl Emit for Material {
+    fn emitted(&self, hit_rec: &HitRecord) -> Vector3<f64> {
+        match self {
+            Material::Emissive(emissive) => emissive.emitted(hit_rec),
+            _ => Vector3::zeros(),
         }
     }
 }
 
 #[derive(Builder, Debug)]
 pub struct Diffuse {
-    #[builder(default = "vector![1.0, 1.0, 1.0]")]
-    pub albedo: Vector3<f64>,
+    #[builder(setter(into), default = "vector![1.0, 1.0, 1.0].into()")]
+    pub albedo: TextureKind,
+    #[builder(setter(strip_option), default)]
+    pub normal_map: Option<NormalMap>,
 }
 
-impl Scatter for Diffuse {
-    fn scatter(&self, _ray: &Ray, hit_rec: &HitRecord) -> (Option<Ray>, &Vector3<f64>) {
-        // let mut rng = thread_rng();
-        // let choice =rng.gen_bool(0.75);
-        if true {
-            let scattered_ray = Ray {
-                direction: hit_rec.normal + random_in_unit_sphere().normalize(),
-                origin: hit_rec.point,
-            };
-            (Some(scattered_ray), &self.albedo)
-        } else {
-            (None, &self.albedo)
+impl Bsdf for Diffuse {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        let mut direction = hit_rec.normal + random_in_unit_sphere().normalize();
+        if direction.is_near_zero() {
+            direction = hit_rec.normal;
         }
+        let direction = direction.normalize();
+        Some(BsdfSample {
+            direction,
+            // The cosine and 1/π cancel with the pdf
+            weight: self.albedo.value(hit_rec),
+            pdf: self.pdf(ray, hit_rec, &direction),
+            is_specular: false,
+            wavelengths: ray.wavelengths,
+        })
+    }
+
+    fn eval(&self, _ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        let cosine = hit_rec.normal.dot(&direction.normalize()).max(0.0);
+        self.albedo.value(hit_rec) * cosine / PI
+    }
+
+    fn pdf(&self, _ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        // normal + a random unit vector is cosine distributed
+        hit_rec.normal.dot(&direction.normalize()).max(0.0) / PI
+    }
+
+    fn is_specular(&self) -> bool {
+        false
     }
 }
 
+/// A GGX microfacet conductor.
 #[derive(Builder, Debug)]
 pub struct Metal {
-    #[builder(default = "vector![1.0, 1.0, 1.0]")]
-    pub albedo: Vector3<f64>,
+    /// Reflectance at normal incidence, or a tint on top of the `conductor` Fresnel
+    #[builder(setter(into), default = "vector![1.0, 1.0, 1.0].into()")]
+    pub albedo: TextureKind,
+    #[builder(setter(strip_option), default)]
+    pub normal_map: Option<NormalMap>,
+    #[builder(default = "0.0")]
+    pub roughness: f64,
+    /// Stretches highlights along the surface tangent, in [0, 1)
     #[builder(default = "0.0")]
-    pub fuzziness: f64,
+    pub anisotropy: f64,
+    #[builder(setter(strip_option), default)]
+    pub conductor: Option<Conductor>,
+    #[builder(setter(strip_option), default)]
+    pub thin_film: Option<ThinFilm>,
+}
+
+impl Metal {
+    fn fresnel(&self, ray: &Ray, hit_rec: &HitRecord, cos_theta: f64) -> Vector3<f64> {
+        let albedo = self.albedo.value(hit_rec);
+        match (&self.conductor, &self.thin_film) {
+            (Some(conductor), None) => {
+                fresnel_conductor(cos_theta, &conductor.eta, &conductor.k).component_mul(&albedo)
+            }
+            (None, None) => fresnel_schlick(&albedo, cos_theta),
+            (Some(conductor), Some(film)) => film
+                .reflectance(
+                    cos_theta,
+                    1.0,
+                    |wavelength| conductor.ir_at(wavelength),
+                    ray.wavelengths,
+                )
+                .component_mul(&albedo),
+            (None, Some(film)) => {
+                // A real index of refraction with the same reflectance at normal incidence
+                let ir = |wavelength| {
+                    let r0 = upsample(&albedo, wavelength).clamp(0.0, 0.999).sqrt();
+                    Complex::new((1.0 + r0) / (1.0 - r0), 0.0)
+                };
+                film.reflectance(cos_theta, 1.0, ir, ray.wavelengths)
+            }
+        }
+    }
+
+    fn distribution(&self) -> Ggx {
+        Ggx::new(self.roughness, self.anisotropy)
+    }
 }
 
-impl Scatter for Metal {
-    fn scatter(&self, ray: &Ray, hit_rec: &HitRecord) -> (Option<Ray>, &Vector3<f64>) {
-        let scattered_direction = ray.direction.normalize().reflect(&hit_rec.normal)
-            + self.fuzziness * random_in_unit_sphere();
-        if scattered_direction.dot(&hit_rec.normal) < 0.0 {
-            return (None, &self.albedo);
+impl Bsdf for Metal {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        let wo = to_local(hit_rec, &-ray.direction.normalize());
+        if wo[2] <= 0.0 {
+            return None;
+        }
+        let ggx = self.distribution();
+        if ggx.is_specular() {
+            return Some(BsdfSample {
+                direction: ray.direction.normalize().reflect(&hit_rec.normal),
+                weight: self.fresnel(ray, hit_rec, wo[2]),
+                pdf: 1.0,
+                is_specular: true,
+                wavelengths: ray.wavelengths,
+            });
         }
-        let scattered_ray = Ray {
-            direction: scattered_direction,
-            origin: hit_rec.point,
+
+        let half = ggx.sample_visible(&wo);
+        let wi = (-wo).reflect(&half);
+        if wi[2] <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: from_local(hit_rec, &wi),
+            weight: self.fresnel(ray, hit_rec, wo.dot(&half)) * ggx.g(&wo, &wi) / ggx.g1(&wo),
+            pdf: ggx.pdf_visible(&wo, &half) / (4.0 * wo.dot(&half)),
+            is_specular: false,
+            wavelengths: ray.wavelengths,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        let wo = to_local(hit_rec, &-ray.direction.normalize());
+        let wi = to_local(hit_rec, &direction.normalize());
+        if wo[2] <= 0.0 || wi[2] <= 0.0 {
+            return Vector3::zeros();
+        }
+        let ggx = self.distribution();
+        let half = (wo + wi).normalize();
+        self.fresnel(ray, hit_rec, wo.dot(&half)) * ggx.d(&half) * ggx.g(&wo, &wi) / (4.0 * wo[2])
+    }
+
+    fn pdf(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        let wo = to_local(hit_rec, &-ray.direction.normalize());
+        let wi = to_local(hit_rec, &direction.normalize());
+        if wo[2] <= 0.0 || wi[2] <= 0.0 {
+            return 0.0;
+        }
+        let half = (wo + wi).normalize();
+        self.distribution().pdf_visible(&wo, &half) / (4.0 * wo.dot(&half))
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distribution().is_specular()
+    }
+}
+
+/// Complex index of refraction `eta + ik` of a conductor, sampled at red, green and blue.
+#[derive(Debug, Clone)]
+pub struct Conductor {
+    pub eta: Vector3<f64>,
+    pub k: Vector3<f64>,
+}
+
+impl Conductor {
+    pub const PRESETS: [&'static str; 4] = ["gold", "copper", "aluminium", "silver"];
+
+    pub fn preset(name: &str) -> Option<Self> {
+        let (eta, k) = match name {
+            "gold" => ([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+            "copper" => ([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+            "aluminium" => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+            "silver" => ([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+            _ => return None,
         };
-        (Some(scattered_ray), &self.albedo)
+        Some(Conductor {
+            eta: eta.into(),
+            k: k.into(),
+        })
+    }
+
+    /// The complex index of refraction at `wavelength` in nanometers, upsampled from RGB.
+    pub fn ir_at(&self, wavelength: f64) -> Complex<f64> {
+        Complex::new(
+            upsample(&self.eta, wavelength),
+            upsample(&self.k, wavelength),
+        )
     }
 }
 
@@ -69,8 +324,29 @@ impl Scatter for Metal {
 pub struct Glass {
     #[builder(default = "1.5")]
     pub ir: f64,
-    #[builder(default = "vector![1.0, 1.0, 1.0]")]
-    pub albedo: Vector3<f64>,
+    #[builder(setter(into), default = "vector![1.0, 1.0, 1.0].into()")]
+    pub albedo: TextureKind,
+    #[builder(setter(strip_option), default)]
+    pub normal_map: Option<NormalMap>,
+    /// Frosts the surface with GGX microfacets, 0 is perfectly smooth
+    #[builder(default = "0.0")]
+    pub roughness: f64,
+    /// Beer-Lambert absorption coefficient of the interior, per unit length
+    #[builder(default = "Vector3::zeros()")]
+    pub absorption: Vector3<f64>,
+    /// Replaces `ir` with a wavelength dependent index of refraction
+    #[builder(setter(strip_option), default)]
+    pub dispersion: Option<Dispersion>,
+    /// A coating on the outside of the surface
+    #[builder(setter(strip_option), default)]
+    pub thin_film: Option<ThinFilm>,
+}
+
+impl GlassBuilder {
+    /// Absorb so that light keeps `color` of its intensity after traveling `distance`.
+    pub fn transmission_color(&mut self, color: Vector3<f64>, distance: f64) -> &mut Self {
+        self.absorption(color.map(|x| -x.max(1e-6).ln() / distance))
+    }
 }
 
 impl Glass {
@@ -80,18 +356,64 @@ impl Glass {
         r0 = r0.powi(2);
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
-}
 
-impl Scatter for Glass {
-    fn scatter(&self, ray: &Ray, hit_rec: &HitRecord) -> (Option<Ray>, &Vector3<f64>) {
-        let mut rng = thread_rng();
-        let irs;
+    fn distribution(&self) -> Ggx {
+        Ggx::new(self.roughness, 0.0)
+    }
+
+    pub fn ir_at(&self, wavelength: Option<f64>) -> f64 {
+        match self.dispersion {
+            Some(ref dispersion) => {
+                dispersion.ir(wavelength.unwrap_or(Dispersion::REFERENCE_WAVELENGTH))
+            }
+            None => self.ir,
+        }
+    }
+
+    /// The wavelength the path has been split off to, if any.
+    fn wavelength(ray: &Ray) -> Option<f64> {
+        ray.wavelengths
+            .filter(Wavelengths::is_single)
+            .map(|wavelengths| wavelengths.hero())
+    }
+
+    /// Fresnel reflectance towards a normal at `cos_theta` from the incoming direction, gray
+    /// unless the surface is coated.
+    fn fresnel(&self, ray: &Ray, hit_rec: &HitRecord, cos_theta: f64, eta: f64) -> Vector3<f64> {
+        match self.thin_film {
+            Some(ref film) => {
+                let ir = self.ir_at(Self::wavelength(ray));
+                let (outside, inside) = if hit_rec.front_face {
+                    (1.0, ir)
+                } else {
+                    (ir, 1.0)
+                };
+                let substrate = |_| Complex::new(inside, 0.0);
+                film.reflectance(cos_theta, outside, substrate, ray.wavelengths)
+            }
+            None => Vector3::repeat(fresnel_dielectric(cos_theta, eta)),
+        }
+    }
+
+    /// Ratio of the index of refraction on the far side to the one on the near side.
+    fn relative_ir(&self, ray: &Ray, hit_rec: &HitRecord) -> f64 {
+        let ir = self.ir_at(Self::wavelength(ray));
         if hit_rec.front_face {
-            irs = (1.0, self.ir);
+            ir
         } else {
-            // TODO a better way?
-            irs = (self.ir * self.ir, self.ir);
+            1.0 / ir
         }
+    }
+
+    fn sample_smooth(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        let mut rng = render_rng();
+        let ir = self.ir_at(Self::wavelength(ray));
+        let irs = if hit_rec.front_face {
+            (1.0, ir)
+        } else {
+            // TODO a better way?
+            (ir * ir, ir)
+        };
 
         let cos_theta = min_by(
             (-ray.direction.normalize()).dot(&hit_rec.normal),
@@ -99,30 +421,463 @@ impl Scatter for Glass {
             |x, y| x.partial_cmp(y).expect("Comparing NaN values!"),
         );
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
-        let out_ray;
-        if irs.0 / irs.1 * sin_theta > 1.0
-            || Self::reflectance(cos_theta, irs.0 / irs.1) > rng.gen::<f64>()
-        {
-            out_ray = Ray {
-                direction: ray.direction.normalize().reflect(&hit_rec.normal),
-                origin: hit_rec.point,
-            };
+        let reflectance = if irs.0 / irs.1 * sin_theta > 1.0 {
+            Vector3::repeat(1.0)
+        } else if self.thin_film.is_some() {
+            self.fresnel(ray, hit_rec, cos_theta, irs.1 / irs.0)
         } else {
-            out_ray = Ray {
-                direction: ray.direction.normalize().refract(&hit_rec.normal, irs),
-                origin: hit_rec.point,
-            };
+            Vector3::repeat(Self::reflectance(cos_theta, irs.0 / irs.1))
+        };
+        // Choose by the average, a coating makes the reflectance colored
+        let reflect_prob = reflectance.mean();
+        let (direction, pdf, weight) = if reflect_prob > rng.gen::<f64>() {
+            (
+                ray.direction.normalize().reflect(&hit_rec.normal),
+                reflect_prob,
+                reflectance / reflect_prob,
+            )
+        } else {
+            (
+                ray.direction.normalize().refract(&hit_rec.normal, irs),
+                1.0 - reflect_prob,
+                (Vector3::repeat(1.0) - reflectance) / (1.0 - reflect_prob),
+            )
+        };
+        Some(BsdfSample {
+            direction: direction.normalize(),
+            weight: self.albedo.value(hit_rec).component_mul(&weight),
+            pdf,
+            is_specular: true,
+            wavelengths: ray.wavelengths,
+        })
+    }
+
+    /// The microfacet normal for which `wo` scatters into `wi`, facing +Z, and whether
+    /// that is a reflection.
+    fn half_vector(&self, wo: &Vector3<f64>, wi: &Vector3<f64>, eta: f64) -> (Vector3<f64>, bool) {
+        let reflect = wi[2] > 0.0;
+        let half = if reflect { wo + wi } else { wo + eta * wi };
+        let half = half.normalize();
+        (if half[2] < 0.0 { -half } else { half }, reflect)
+    }
+
+    /// Density of sampling `wi` through the microfacet `half`, the average Fresnel term is
+    /// the probability of choosing reflection.
+    fn pdf_local(
+        &self,
+        ray: &Ray,
+        hit_rec: &HitRecord,
+        wo: &Vector3<f64>,
+        wi: &Vector3<f64>,
+        eta: f64,
+    ) -> f64 {
+        let ggx = self.distribution();
+        let (half, reflect) = self.half_vector(wo, wi, eta);
+        let (cos_o, cos_i) = (wo.dot(&half), wi.dot(&half));
+        // Microfacets facing away from either direction
+        if cos_o <= 0.0 || (cos_i > 0.0) != reflect {
+            return 0.0;
+        }
+        let fresnel = self.fresnel(ray, hit_rec, cos_o, eta).mean();
+        let pdf_half = ggx.pdf_visible(wo, &half);
+        if reflect {
+            fresnel * pdf_half / (4.0 * cos_o)
+        } else {
+            let denom = (cos_i + cos_o / eta).powi(2);
+            (1.0 - fresnel) * pdf_half * cos_i.abs() / denom
         }
-        (Some(out_ray), &self.albedo)
     }
 }
 
-impl<T: Scatter> Scatter for &T {
-    fn scatter(&self, ray: &Ray, hit_rec: &HitRecord) -> (Option<Ray>, &Vector3<f64>) {
-        (*self).scatter(ray, hit_rec)
+impl Bsdf for Glass {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        if self.dispersion.is_some() {
+            // An RGB path is continued spectrally, the renderer converts it back to RGB
+            let wavelengths = ray.wavelengths.unwrap_or_else(Wavelengths::sample);
+            // Only the hero wavelength can follow the refracted direction
+            if !wavelengths.is_single() {
+                let ray = Ray {
+                    origin: ray.origin,
+                    direction: ray.direction,
+                    wavelengths: Some(wavelengths.terminate_secondary()),
+                };
+                let mut sample = self.sample(&ray, hit_rec)?;
+                sample.weight *= HERO_WAVELENGTHS as f64;
+                return Some(sample);
+            }
+        }
+        let ggx = self.distribution();
+        if ggx.is_specular() {
+            return self.sample_smooth(ray, hit_rec);
+        }
+
+        let eta = self.relative_ir(ray, hit_rec);
+        let wo = to_local(hit_rec, &-ray.direction.normalize());
+        if wo[2] <= 0.0 {
+            return None;
+        }
+        let half = ggx.sample_visible(&wo);
+        let cos_o = wo.dot(&half);
+        let fresnel = self.fresnel(ray, hit_rec, cos_o, eta);
+        let reflect_prob = fresnel.mean();
+        let (wi, selected) = if render_rng().gen::<f64>() < reflect_prob {
+            let wi = (-wo).reflect(&half);
+            if wi[2] <= 0.0 {
+                return None;
+            }
+            (wi, fresnel / reflect_prob)
+        } else {
+            // Snell's law around the microfacet normal, total internal reflection has a
+            // Fresnel term of 1 so it can't get here
+            let sin2_t = (1.0 - cos_o.powi(2)) / eta.powi(2);
+            let cos_t = (1.0 - sin2_t).max(0.0).sqrt();
+            let wi = -wo / eta + (cos_o / eta - cos_t) * half;
+            if wi[2] >= 0.0 {
+                return None;
+            }
+            let transmittance = Vector3::repeat(1.0) - fresnel;
+            (wi, transmittance / (1.0 - reflect_prob))
+        };
+        // The Fresnel term cancels with the choice between reflection and refraction, up to
+        // the color of a coating
+        Some(BsdfSample {
+            direction: from_local(hit_rec, &wi),
+            weight: self.albedo.value(hit_rec).component_mul(&selected) * ggx.g(&wo, &wi)
+                / ggx.g1(&wo),
+            pdf: self.pdf_local(ray, hit_rec, &wo, &wi, eta),
+            is_specular: false,
+            wavelengths: ray.wavelengths,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        let ggx = self.distribution();
+        let eta = self.relative_ir(ray, hit_rec);
+        let wo = to_local(hit_rec, &-ray.direction.normalize());
+        let wi = to_local(hit_rec, &direction.normalize());
+        if ggx.is_specular() || wo[2] <= 0.0 || wi[2] == 0.0 {
+            return Vector3::zeros();
+        }
+        let (half, reflect) = self.half_vector(&wo, &wi, eta);
+        let (cos_o, cos_i) = (wo.dot(&half), wi.dot(&half));
+        if cos_o <= 0.0 || (cos_i > 0.0) != reflect {
+            return Vector3::zeros();
+        }
+        let fresnel = self.fresnel(ray, hit_rec, cos_o, eta);
+        let albedo = self.albedo.value(hit_rec);
+        let dg = ggx.d(&half) * ggx.g(&wo, &wi);
+        if reflect {
+            albedo.component_mul(&fresnel) * dg / (4.0 * wo[2])
+        } else {
+            let denom = (cos_i + cos_o / eta).powi(2);
+            albedo.component_mul(&(Vector3::repeat(1.0) - fresnel)) * dg * (cos_i * cos_o).abs()
+                / (wo[2] * denom)
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        let wo = to_local(hit_rec, &-ray.direction.normalize());
+        let wi = to_local(hit_rec, &direction.normalize());
+        if self.is_specular() || wo[2] <= 0.0 || wi[2] == 0.0 {
+            return 0.0;
+        }
+        self.pdf_local(ray, hit_rec, &wo, &wi, self.relative_ir(ray, hit_rec))
     }
+
+    fn is_specular(&self) -> bool {
+        self.distribution().is_specular()
+    }
+}
+
+/// Scatters equally in all directions, the phase function of a participating medium.
+#[derive(Builder, Debug)]
+pub struct Isotropic {
+    /// Fraction of the light that is scattered rather than absorbed
+    #[builder(setter(into), default = "vector![1.0, 1.0, 1.0].into()")]
+    pub albedo: TextureKind,
+}
+
+impl Bsdf for Isotropic {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        Some(BsdfSample {
+            direction: random_in_unit_sphere().normalize(),
+            weight: self.albedo.value(hit_rec),
+            pdf: 1.0 / (4.0 * PI),
+            is_specular: false,
+            wavelengths: ray.wavelengths,
+        })
+    }
+
+    fn eval(&self, _ray: &Ray, hit_rec: &HitRecord, _direction: &Vector3<f64>) -> Vector3<f64> {
+        // No cosine, there is no surface
+        self.albedo.value(hit_rec) / (4.0 * PI)
+    }
+
+    fn pdf(&self, _ray: &Ray, _hit_rec: &HitRecord, _direction: &Vector3<f64>) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+/// The Henyey-Greenstein phase function, scattering mostly forward for a positive `asymmetry`
+/// like clouds and mostly backward for a negative one.
+#[derive(Builder, Debug)]
+pub struct HenyeyGreenstein {
+    /// Fraction of the light that is scattered rather than absorbed
+    #[builder(setter(into), default = "vector![1.0, 1.0, 1.0].into()")]
+    pub albedo: TextureKind,
+    /// The average cosine of the scattering angle, in (-1, 1)
+    #[builder(default = "0.0")]
+    pub asymmetry: f64,
 }
 
-pub trait Scatter {
-    fn scatter(&self, ray: &Ray, hit_rec: &HitRecord) -> (Option<Ray>, &Vector3<f64>);
+impl HenyeyGreenstein {
+    /// Density of turning by an angle with `cos_theta`, per unit solid angle.
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.asymmetry;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.max(1e-12).sqrt())
+    }
+}
+
+impl Bsdf for HenyeyGreenstein {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        let mut rng = render_rng();
+        let g = self.asymmetry;
+        let u = rng.gen::<f64>();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let ratio = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+            ((1.0 + g * g - ratio * ratio) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+
+        // Around the direction of travel
+        let forward = ray.direction.normalize();
+        let axis = if forward[0].abs() > 0.9 {
+            vector![0.0, 1.0, 0.0]
+        } else {
+            vector![1.0, 0.0, 0.0]
+        };
+        let tangent = forward.cross(&axis).normalize();
+        let bitangent = forward.cross(&tangent);
+        let direction =
+            sin_theta * (phi.cos() * tangent + phi.sin() * bitangent) + cos_theta * forward;
+        Some(BsdfSample {
+            direction,
+            // The phase function cancels with the pdf
+            weight: self.albedo.value(hit_rec),
+            pdf: self.phase(cos_theta),
+            is_specular: false,
+            wavelengths: ray.wavelengths,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        self.albedo.value(hit_rec) * self.pdf(ray, hit_rec, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, _hit_rec: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        self.phase(ray.direction.normalize().dot(&direction.normalize()))
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Builder, Debug)]
+pub struct Emissive {
+    #[builder(default = "vector![1.0, 1.0, 1.0]")]
+    pub color: Vector3<f64>,
+    #[builder(default = "1.0")]
+    pub strength: f64,
+}
+
+impl Bsdf for Emissive {
+    fn sample(&self, _ray: &Ray, _hit_rec: &HitRecord) -> Option<BsdfSample> {
+        None
+    }
+}
+
+impl Emit for Emissive {
+    fn emitted(&self, _hit_rec: &HitRecord) -> Vector3<f64> {
+        self.strength * self.color
+    }
+}
+
+pub trait Emit {
+    fn emitted(&self, hit_rec: &HitRecord) -> Vector3<f64>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ray hitting the XY plane from above at an angle, with the tangent along +X.
+    fn surface(front_face: bool) -> (Ray, HitRecord<'static>) {
+        let mut hit_rec = HitRecord::new(&Material::None);
+        hit_rec.normal = vector![0.0, 0.0, 1.0];
+        hit_rec.tangent = vector![1.0, 0.0, 0.0];
+        hit_rec.bitangent = vector![0.0, 1.0, 0.0];
+        hit_rec.front_face = front_face;
+        let ray = Ray {
+            origin: vector![-1.0, 0.5, 2.0],
+            direction: vector![1.0, -0.5, -2.0],
+            wavelengths: None,
+        };
+        (ray, hit_rec)
+    }
+
+    /// Every sample's pdf and weight must agree with `pdf` and `eval` in its direction.
+    fn assert_consistent(bsdf: &impl Bsdf, ray: &Ray, hit_rec: &HitRecord) {
+        seed_render_rng(1);
+        let mut samples = 0;
+        for _ in 0..1000 {
+            let sample = match bsdf.sample(ray, hit_rec) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            samples += 1;
+            assert!(!sample.is_specular);
+            let pdf = bsdf.pdf(ray, hit_rec, &sample.direction);
+            assert!(
+                (pdf - sample.pdf).abs() <= 1e-9 * pdf,
+                "{} {}",
+                pdf,
+                sample.pdf
+            );
+            let weight = bsdf.eval(ray, hit_rec, &sample.direction) / pdf;
+            assert!(
+                (weight - sample.weight).norm() <= 1e-9 * weight.norm(),
+                "{} {}",
+                weight,
+                sample.weight
+            );
+        }
+        assert!(samples > 900);
+    }
+
+    #[test]
+    fn diffuse_sample_matches_eval_and_pdf() {
+        let diffuse = DiffuseBuilder::default()
+            .albedo(vector![0.8, 0.5, 0.2])
+            .build()
+            .unwrap();
+        let (ray, hit_rec) = surface(true);
+        assert_consistent(&diffuse, &ray, &hit_rec);
+        // Nothing goes below the surface
+        let below = vector![0.3, 0.0, -1.0];
+        assert_eq!(diffuse.eval(&ray, &hit_rec, &below), Vector3::zeros());
+        assert_eq!(diffuse.pdf(&ray, &hit_rec, &below), 0.0);
+    }
+
+    #[test]
+    fn rough_metal_sample_matches_eval_and_pdf() {
+        for anisotropy in [0.0, 0.6] {
+            let metal = MetalBuilder::default()
+                .albedo(vector![0.9, 0.6, 0.3])
+                .roughness(0.4)
+                .anisotropy(anisotropy)
+                .conductor(Conductor::preset("copper").unwrap())
+                .build()
+                .unwrap();
+            let (ray, hit_rec) = surface(true);
+            assert_consistent(&metal, &ray, &hit_rec);
+        }
+    }
+
+    #[test]
+    fn rough_glass_sample_matches_eval_and_pdf() {
+        let glass = GlassBuilder::default()
+            .albedo(vector![0.9, 0.95, 1.0])
+            .roughness(0.3)
+            .build()
+            .unwrap();
+        // Entering and leaving the glass, where some of it is totally internally reflected
+        for front_face in [true, false] {
+            let (ray, hit_rec) = surface(front_face);
+            assert_consistent(&glass, &ray, &hit_rec);
+            seed_render_rng(2);
+            let refracted = (0..1000)
+                .filter_map(|_| glass.sample(&ray, &hit_rec))
+                .filter(|sample| sample.direction[2] < 0.0)
+                .count();
+            assert!(refracted > 100 && refracted < 1000, "{}", refracted);
+        }
+    }
+
+    #[test]
+    fn beer_lambert_absorption() {
+        let glass = Material::Glass(
+            GlassBuilder::default()
+                .transmission_color(vector![0.8, 0.5, 0.1], 2.0)
+                .build()
+                .unwrap(),
+        );
+        let (_, mut hit_rec) = surface(false);
+        let transmittance = glass.transmittance(&hit_rec, 2.0);
+        assert!((transmittance - vector![0.8, 0.5, 0.1]).norm() < 1e-12);
+        // Twice the distance squares it
+        let transmittance = glass.transmittance(&hit_rec, 4.0);
+        assert!((transmittance - vector![0.64, 0.25, 0.01]).norm() < 1e-12);
+        // Segments ending on the outside ran through air
+        hit_rec.front_face = true;
+        assert_eq!(glass.transmittance(&hit_rec, 2.0), vector![1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn isotropic_sample_matches_eval_and_pdf() {
+        let isotropic = IsotropicBuilder::default()
+            .albedo(vector![0.9, 0.8, 0.7])
+            .build()
+            .unwrap();
+        let (ray, hit_rec) = surface(true);
+        assert_consistent(&isotropic, &ray, &hit_rec);
+    }
+
+    #[test]
+    fn henyey_greenstein_sample_matches_eval_and_pdf() {
+        for asymmetry in [-0.5, 0.0, 0.8] {
+            let phase = HenyeyGreensteinBuilder::default()
+                .albedo(vector![0.9, 0.8, 0.7])
+                .asymmetry(asymmetry)
+                .build()
+                .unwrap();
+            let (ray, hit_rec) = surface(true);
+            assert_consistent(&phase, &ray, &hit_rec);
+            // Normalized over the sphere, with the asymmetry as its mean cosine
+            let steps = 10000;
+            let (mut total, mut mean_cos) = (0.0, 0.0);
+            for i in 0..steps {
+                let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / steps as f64;
+                let p = phase.phase(cos_theta) * 4.0 * PI / steps as f64;
+                total += p;
+                mean_cos += p * cos_theta;
+            }
+            assert!((total - 1.0).abs() < 1e-4, "{} {}", asymmetry, total);
+            assert!(
+                (mean_cos - asymmetry).abs() < 1e-4,
+                "{} {}",
+                asymmetry,
+                mean_cos
+            );
+        }
+    }
+
+    #[test]
+    fn specular_by_default() {
+        let emissive = EmissiveBuilder::default().build().unwrap();
+        let (ray, hit_rec) = surface(true);
+        let up = vector![0.0, 0.0, 1.0];
+        assert!(emissive.is_specular());
+        assert!(emissive.sample(&ray, &hit_rec).is_none());
+        assert_eq!(emissive.eval(&ray, &hit_rec, &up), Vector3::zeros());
+        assert_eq!(emissive.pdf(&ray, &hit_rec, &up), 0.0);
+    }
 }
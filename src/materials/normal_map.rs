@@ -0,0 +1,51 @@
+use super::*;
+
+/// Perturbs the shading normal with detail from a texture.
+#[derive(Debug, Clone)]
+pub enum NormalMap {
+    /// Tangent space normals encoded as colors, loaded without sRGB decoding
+    Tangent(TextureKind),
+    /// Heights from the red channel, `strength` scales the slopes
+    Bump { height: TextureKind, strength: f64 },
+}
+
+impl NormalMap {
+    // Offset in UV space for the finite differences of bump maps
+    const DELTA: f64 = 0.0005;
+
+    pub fn apply(&self, hit_rec: &mut HitRecord) {
+        let sign = if hit_rec.front_face { 1.0 } else { -1.0 };
+        let normal = sign * hit_rec.normal;
+        let (tangent, bitangent) = hit_rec.tangent_frame();
+
+        let perturbed = match self {
+            NormalMap::Tangent(texture) => {
+                let color = texture.value(hit_rec);
+                let local = color.map(|x| 2.0 * x - 1.0);
+                local[0] * tangent + local[1] * bitangent + local[2] * normal
+            }
+            NormalMap::Bump { height, strength } => {
+                let base = height.value(hit_rec)[0];
+                let mut shifted = hit_rec.clone();
+                shifted.uv = hit_rec.uv + vector![Self::DELTA, 0.0];
+                shifted.point = hit_rec.point + Self::DELTA * hit_rec.tangent;
+                let du = (height.value(&shifted)[0] - base) / Self::DELTA;
+                shifted.uv = hit_rec.uv + vector![0.0, Self::DELTA];
+                shifted.point = hit_rec.point + Self::DELTA * hit_rec.bitangent;
+                let dv = (height.value(&shifted)[0] - base) / Self::DELTA;
+
+                // Slopes relative to the length of the surface derivatives
+                let du = strength * du / hit_rec.tangent.norm().max(f64::EPSILON);
+                let dv = strength * dv / hit_rec.bitangent.norm().max(f64::EPSILON);
+                normal - du * tangent - dv * bitangent
+            }
+        };
+
+        // Keep the geometric side if the map points below the surface
+        if let Some(perturbed) = perturbed.try_normalize(1e-12) {
+            if perturbed.dot(&normal) > 0.0 {
+                hit_rec.normal = sign * perturbed;
+            }
+        }
+    }
+}
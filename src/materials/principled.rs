@@ -0,0 +1,332 @@
+use super::*;
+use std::f64::consts::PI;
+
+/// A principled material in the spirit of Burley's Disney BSDF, with the parameters artists
+/// know from DCC tools. A diffuse lobe with sheen, a dielectric GGX specular lobe, a
+/// metallic GGX lobe and rough transmission are weighted by `metallic` and
+/// `transmission` and layered under a GGX clearcoat.
+#[derive(Builder, Debug)]
+#[builder(build_fn(skip))]
+// Parameters that only go into `lobes` are kept for the builder and for `Debug`
+#[allow(dead_code)]
+pub struct Principled {
+    #[builder(setter(into))]
+    base_color: TextureKind,
+    /// Blends from a dielectric to a conductor with `base_color` as its reflectance
+    metallic: f64,
+    roughness: f64,
+    /// Stretches highlights along the surface tangent, in [0, 1)
+    anisotropy: f64,
+    /// Dielectric reflectance at normal incidence, 0.5 is 4%
+    specular: f64,
+    /// Blends the dielectric base from diffuse to transmissive
+    transmission: f64,
+    /// Index of refraction of the transmissive base
+    ir: f64,
+    clearcoat: f64,
+    clearcoat_roughness: f64,
+    /// Retroreflective fuzz at grazing angles, like on cloth
+    sheen: f64,
+    /// Blends the sheen from white to the hue of `base_color`
+    sheen_tint: f64,
+    #[builder(setter(strip_option))]
+    normal_map: Option<NormalMap>,
+
+    #[builder(setter(skip))]
+    lobes: Box<Lobes>,
+}
+
+/// The lobes besides the diffuse one, built from the parameters of `Principled`.
+#[derive(Debug)]
+struct Lobes {
+    dielectric: Metal,
+    conductor: Metal,
+    glass: Glass,
+    coat: Metal,
+}
+
+impl PrincipledBuilder {
+    /// Roughness below this is raised to it, so that every lobe can be evaluated.
+    const MIN_ROUGHNESS: f64 = 0.05;
+
+    pub fn build(&self) -> Result<Principled, PrincipledBuilderError> {
+        let base_color = match self.base_color {
+            Some(ref value) => value.clone(),
+            None => vector![0.8, 0.8, 0.8].into(),
+        };
+        let unit = |value: Option<f64>, default: f64| value.unwrap_or(default).clamp(0.0, 1.0);
+        let roughness = unit(self.roughness, 0.5).max(Self::MIN_ROUGHNESS);
+        let anisotropy = self.anisotropy.unwrap_or(0.0).clamp(0.0, 0.99);
+        let specular = unit(self.specular, 0.5);
+        let ir = self.ir.unwrap_or(1.5);
+        let clearcoat_roughness = unit(self.clearcoat_roughness, 0.05).max(Self::MIN_ROUGHNESS);
+
+        let specular_lobe = |albedo: TextureKind, roughness: f64, anisotropy: f64| {
+            MetalBuilder::default()
+                .albedo(albedo)
+                .roughness(roughness)
+                .anisotropy(anisotropy)
+                .build()
+                .unwrap()
+        };
+        let lobes = Lobes {
+            dielectric: specular_lobe(
+                Vector3::repeat(0.08 * specular).into(),
+                roughness,
+                anisotropy,
+            ),
+            conductor: specular_lobe(base_color.clone(), roughness, anisotropy),
+            glass: GlassBuilder::default()
+                .ir(ir)
+                .albedo(base_color.clone())
+                .roughness(roughness)
+                .build()
+                .unwrap(),
+            // Polyurethane, an index of refraction of 1.5
+            coat: specular_lobe(Vector3::repeat(0.04).into(), clearcoat_roughness, 0.0),
+        };
+        Ok(Principled {
+            base_color,
+            metallic: unit(self.metallic, 0.0),
+            roughness,
+            anisotropy,
+            specular,
+            transmission: unit(self.transmission, 0.0),
+            ir,
+            clearcoat: unit(self.clearcoat, 0.0),
+            clearcoat_roughness,
+            sheen: unit(self.sheen, 0.0),
+            sheen_tint: unit(self.sheen_tint, 0.5),
+            normal_map: self.normal_map.clone().unwrap_or(None),
+            lobes: Box::new(lobes),
+        })
+    }
+}
+
+impl Principled {
+    /// Whether some light goes through the base instead of being reflected.
+    pub fn transmits(&self) -> bool {
+        self.transmission > 0.0
+    }
+
+    pub fn normal_map(&self) -> Option<&NormalMap> {
+        self.normal_map.as_ref()
+    }
+}
+
+/// The lobes of `Principled`, in the order of `Principled::weights`.
+const LOBES: usize = 5;
+const DIFFUSE: usize = 0;
+const DIELECTRIC: usize = 1;
+const CONDUCTOR: usize = 2;
+const GLASS: usize = 3;
+const COAT: usize = 4;
+
+impl Principled {
+    /// Weights of the lobes for light leaving along `ray` reversed, and the probabilities
+    /// with which they are sampled.
+    fn weights(&self, ray: &Ray, hit_rec: &HitRecord) -> ([f64; LOBES], [f64; LOBES]) {
+        let mut weights = [0.0; LOBES];
+        let mut probabilities = [0.0; LOBES];
+        if !hit_rec.front_face && self.transmission > 0.0 {
+            // Only light that was transmitted gets inside
+            weights[GLASS] = 1.0;
+            probabilities[GLASS] = 1.0;
+            return (weights, probabilities);
+        }
+        let cos_o = (-ray.direction.normalize()).dot(&hit_rec.normal);
+        let coat = self.clearcoat * schlick(0.04, cos_o);
+        let base = 1.0 - coat;
+        weights[DIFFUSE] = base * (1.0 - self.metallic) * (1.0 - self.transmission);
+        weights[DIELECTRIC] = weights[DIFFUSE];
+        weights[CONDUCTOR] = base * self.metallic;
+        weights[GLASS] = base * (1.0 - self.metallic) * self.transmission;
+        weights[COAT] = self.clearcoat;
+
+        // Roughly by how much light each lobe reflects
+        let fresnel = schlick(0.08 * self.specular, cos_o);
+        probabilities[DIFFUSE] = weights[DIFFUSE] * (1.0 - fresnel);
+        probabilities[DIELECTRIC] = weights[DIELECTRIC] * fresnel;
+        probabilities[CONDUCTOR] = weights[CONDUCTOR];
+        probabilities[GLASS] = weights[GLASS];
+        probabilities[COAT] = coat;
+        let total: f64 = probabilities.iter().sum();
+        if total > 0.0 {
+            probabilities.iter_mut().for_each(|p| *p /= total);
+        }
+        (weights, probabilities)
+    }
+
+    /// The diffuse lobe with sheen, times the cosine term. The diffuse part only gets the
+    /// light the dielectric specular lobe doesn't reflect.
+    fn eval_diffuse(
+        &self,
+        ray: &Ray,
+        hit_rec: &HitRecord,
+        direction: &Vector3<f64>,
+    ) -> Vector3<f64> {
+        let wo = -ray.direction.normalize();
+        let wi = direction.normalize();
+        let cos_i = hit_rec.normal.dot(&wi);
+        if cos_i <= 0.0 {
+            return Vector3::zeros();
+        }
+        let base_color = self.base_color.value(hit_rec);
+        let fresnel = schlick(0.08 * self.specular, hit_rec.normal.dot(&wo));
+        let mut value = (1.0 - fresnel) * base_color / PI;
+        if self.sheen > 0.0 {
+            let luminance = base_color.dot(&vector![0.2126, 0.7152, 0.0722]);
+            let tint = if luminance > 0.0 {
+                base_color / luminance
+            } else {
+                vector![1.0, 1.0, 1.0]
+            };
+            let color = vector![1.0, 1.0, 1.0].lerp(&tint, self.sheen_tint);
+            let half = (wo + wi).normalize();
+            value += self.sheen * (1.0 - half.dot(&wi).clamp(0.0, 1.0)).powi(5) * color;
+        }
+        value * cos_i
+    }
+
+    fn lobe(&self, index: usize) -> &dyn Bsdf {
+        match index {
+            DIELECTRIC => &self.lobes.dielectric,
+            CONDUCTOR => &self.lobes.conductor,
+            GLASS => &self.lobes.glass,
+            COAT => &self.lobes.coat,
+            _ => unreachable!("The diffuse lobe is evaluated directly"),
+        }
+    }
+}
+
+impl Bsdf for Principled {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        let (_, probabilities) = self.weights(ray, hit_rec);
+        let u = render_rng().gen::<f64>();
+        let mut chosen = LOBES - 1;
+        let mut sum = 0.0;
+        for (index, probability) in probabilities.iter().enumerate() {
+            sum += probability;
+            if u < sum {
+                chosen = index;
+                break;
+            }
+        }
+        if probabilities[chosen] <= 0.0 {
+            return None;
+        }
+
+        let direction = if chosen == DIFFUSE {
+            let mut direction = hit_rec.normal + random_in_unit_sphere().normalize();
+            if direction.is_near_zero() {
+                direction = hit_rec.normal;
+            }
+            direction.normalize()
+        } else {
+            self.lobe(chosen).sample(ray, hit_rec)?.direction
+        };
+        // Weighted by all the lobes that could have picked the direction
+        let pdf = self.pdf(ray, hit_rec, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: self.eval(ray, hit_rec, &direction) / pdf,
+            pdf,
+            is_specular: false,
+            wavelengths: ray.wavelengths,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        let (weights, _) = self.weights(ray, hit_rec);
+        let mut value = Vector3::zeros();
+        for (index, weight) in weights.iter().enumerate() {
+            if *weight <= 0.0 {
+                continue;
+            }
+            value += *weight
+                * if index == DIFFUSE {
+                    self.eval_diffuse(ray, hit_rec, direction)
+                } else {
+                    self.lobe(index).eval(ray, hit_rec, direction)
+                };
+        }
+        value
+    }
+
+    fn pdf(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        let (_, probabilities) = self.weights(ray, hit_rec);
+        probabilities
+            .iter()
+            .enumerate()
+            .filter(|(_, probability)| **probability > 0.0)
+            .map(|(index, probability)| {
+                probability
+                    * if index == DIFFUSE {
+                        hit_rec.normal.dot(&direction.normalize()).max(0.0) / PI
+                    } else {
+                        self.lobe(index).pdf(ray, hit_rec, direction)
+                    }
+            })
+            .sum()
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+/// Schlick's approximation for a gray reflectance `f0` at normal incidence.
+fn schlick(f0: f64, cos_theta: f64) -> f64 {
+    fresnel_schlick(&Vector3::repeat(f0), cos_theta)[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_and_limits() {
+        let principled = PrincipledBuilder::default()
+            .roughness(0.0)
+            .metallic(2.0)
+            .build()
+            .unwrap();
+        assert_eq!(principled.roughness, PrincipledBuilder::MIN_ROUGHNESS);
+        assert_eq!(principled.clearcoat_roughness, 0.05);
+        assert_eq!(principled.metallic, 1.0);
+        assert_eq!(principled.specular, 0.5);
+    }
+
+    #[test]
+    fn white_base_conserves_energy() {
+        seed_render_rng(1);
+        let material = Material::None;
+        let mut hit_rec = HitRecord::new(&material);
+        hit_rec.normal = vector![0.0, 0.0, 1.0];
+        hit_rec.tangent = vector![1.0, 0.0, 0.0];
+        hit_rec.bitangent = vector![0.0, 1.0, 0.0];
+        let ray = Ray {
+            origin: vector![0.0, 0.0, 0.0],
+            direction: vector![-0.5, 0.0, -1.0],
+            wavelengths: None,
+        };
+        for clearcoat in [0.0, 1.0] {
+            let principled = PrincipledBuilder::default()
+                .base_color(vector![1.0, 1.0, 1.0])
+                .clearcoat(clearcoat)
+                .build()
+                .unwrap();
+            let samples = 20_000;
+            let albedo: Vector3<f64> = (0..samples)
+                .filter_map(|_| principled.sample(&ray, &hit_rec))
+                .map(|sample| sample.weight)
+                .sum::<Vector3<f64>>()
+                / samples as f64;
+            assert!(albedo.max() < 1.02, "{} {}", clearcoat, albedo);
+            assert!(albedo.min() > 0.8, "{} {}", clearcoat, albedo);
+        }
+    }
+}
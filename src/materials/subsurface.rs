@@ -0,0 +1,190 @@
+use super::*;
+use std::f64::consts::PI;
+use std::sync::Once;
+
+/// Scattering events after which a random walk is ended with Russian roulette.
+const WALK_ROULETTE: u32 = 16;
+/// Scattering events after which a random walk is given up on entirely.
+const WALK_MAX: u32 = 4096;
+
+/// A translucent material like skin, wax, marble or milk. Light that isn't reflected by its
+/// smooth dielectric surface enters the object and takes a random walk through a
+/// homogeneous medium inside it, leaving diffusely wherever the walk crosses the surface
+/// again.
+#[derive(Builder, Debug)]
+pub struct Subsurface {
+    /// Scattering coefficient of the interior per unit length, per RGB channel
+    #[builder(default = "vector![1.0, 1.0, 1.0]")]
+    pub scattering: Vector3<f64>,
+    /// Absorption coefficient of the interior per unit length, per RGB channel
+    #[builder(default = "Vector3::zeros()")]
+    pub absorption: Vector3<f64>,
+    #[builder(default = "1.4")]
+    pub ir: f64,
+}
+
+impl SubsurfaceBuilder {
+    /// Derive the coefficients from the color the material appears to have overall and the
+    /// average distance light travels inside it, with the fit of Chiang et al. 2016,
+    /// "Practical and Controllable Subsurface Scattering for Production Path Tracing".
+    pub fn albedo(&mut self, albedo: Vector3<f64>, mean_free_path: Vector3<f64>) -> &mut Self {
+        let albedo = albedo.map(|a| a.clamp(0.0, 0.999));
+        let single =
+            albedo.map(|a| 1.0 - (-5.09406 * a + 2.61188 * a * a - 4.31805 * a.powi(3)).exp());
+        let extinction = albedo.zip_map(&mean_free_path, |a, d| {
+            1.0 / (d.max(1e-6) * (1.9 - a + 3.5 * (a - 0.8).powi(2)))
+        });
+        let scattering = single.component_mul(&extinction);
+        self.scattering(scattering)
+            .absorption(extinction - scattering)
+    }
+}
+
+impl Subsurface {
+    fn extinction(&self) -> Vector3<f64> {
+        self.scattering + self.absorption
+    }
+
+    /// Follows `ray`, which has just entered the object, through the interior until it
+    /// leaves through a surface of `boundary`. Returns the last segment of the walk, where
+    /// it left, and the throughput of the walk. Paths that are absorbed or run into
+    /// anything but this object's surface end with `None`.
+    pub fn walk<'b, 'a: 'b>(
+        &self,
+        ray: &Ray,
+        boundary: &impl Hit<'b, 'a>,
+    ) -> Option<(Ray, HitRecord<'b>, Vector3<f64>)> {
+        let mut rng = render_rng();
+        let extinction = self.extinction();
+        let mut throughput = vector![1.0, 1.0, 1.0];
+        let mut ray = Ray {
+            origin: ray.origin,
+            direction: ray.direction.normalize(),
+            wavelengths: ray.wavelengths,
+        };
+        // Offset from the surface, a scattering event needs none
+        let mut t_min = 1e-4;
+        for bounce in 0..WALK_MAX {
+            // Sample the distance with one channel's extinction, weighted by the others' too
+            let total = throughput.sum();
+            if total <= 0.0 {
+                return None;
+            }
+            let channel_pdf = throughput / total;
+            let u = rng.gen::<f64>();
+            let channel = if u < channel_pdf[0] {
+                0
+            } else if u < channel_pdf[0] + channel_pdf[1] {
+                1
+            } else {
+                2
+            };
+            let distance = -(1.0 - rng.gen::<f64>()).ln() / extinction[channel];
+
+            let mut hit_rec = HitRecord::new(&Material::None);
+            if boundary.hit(&ray, t_min..distance, &mut hit_rec) {
+                let transmittance = extinction.map(|sigma| (-sigma * hit_rec.t).exp());
+                let pdf = channel_pdf.dot(&transmittance);
+                throughput = throughput.component_mul(&transmittance) / pdf;
+                let own_surface = matches!(
+                    hit_rec.material,
+                    Material::Subsurface(subsurface) if std::ptr::eq(subsurface, self)
+                );
+                if !own_surface || hit_rec.front_face {
+                    return None;
+                }
+                // Reflected back inside by the surface, or out through it
+                let cos_theta = -ray.direction.dot(&hit_rec.normal);
+                if rng.gen::<f64>() < fresnel_dielectric(cos_theta, 1.0 / self.ir) {
+                    ray = Ray {
+                        origin: hit_rec.point,
+                        direction: ray.direction.reflect(&hit_rec.normal),
+                        wavelengths: ray.wavelengths,
+                    };
+                    t_min = 1e-4;
+                    continue;
+                }
+                return Some((ray, hit_rec, throughput));
+            }
+
+            let transmittance = extinction.map(|sigma| (-sigma * distance).exp());
+            let pdf = channel_pdf.dot(&extinction.component_mul(&transmittance));
+            throughput =
+                throughput.component_mul(&self.scattering.component_mul(&transmittance)) / pdf;
+            if bounce >= WALK_ROULETTE {
+                // Only once absorption has taken its toll, deep walks are common in dense media
+                let survival = throughput.max().min(1.0);
+                if rng.gen::<f64>() >= survival {
+                    return None;
+                }
+                throughput /= survival;
+            }
+            ray = Ray {
+                origin: ray.at(distance),
+                direction: random_in_unit_sphere().normalize(),
+                wavelengths: ray.wavelengths,
+            };
+            t_min = 0.0;
+        }
+        static EXHAUSTED: Once = Once::new();
+        EXHAUSTED.call_once(|| {
+            log::warn!(
+                "A subsurface random walk took {} steps without leaving, is the object closed?",
+                WALK_MAX
+            )
+        });
+        None
+    }
+}
+
+/// On the outside the surface is a smooth dielectric transmitting diffusely into the
+/// object. Where a walk leaves, a back face, it transmits diffusely out of it.
+impl Bsdf for Subsurface {
+    fn sample(&self, ray: &Ray, hit_rec: &HitRecord) -> Option<BsdfSample> {
+        let mut transmitted = 1.0;
+        if hit_rec.front_face {
+            let cos_theta = (-ray.direction.normalize()).dot(&hit_rec.normal);
+            let reflectance = fresnel_dielectric(cos_theta, self.ir);
+            if render_rng().gen::<f64>() < reflectance {
+                return Some(BsdfSample {
+                    direction: ray.direction.normalize().reflect(&hit_rec.normal),
+                    weight: vector![1.0, 1.0, 1.0],
+                    pdf: reflectance,
+                    is_specular: true,
+                    wavelengths: ray.wavelengths,
+                });
+            }
+            transmitted = 1.0 - reflectance;
+        }
+        // Cosine distributed to the far side of the surface, into the object or out of it
+        let mut direction = random_in_unit_sphere().normalize() - hit_rec.normal;
+        if direction.is_near_zero() {
+            direction = -hit_rec.normal;
+        }
+        let direction = direction.normalize();
+        Some(BsdfSample {
+            direction,
+            // The cosine and 1/π cancel with the pdf
+            weight: vector![1.0, 1.0, 1.0],
+            pdf: transmitted * (-hit_rec.normal).dot(&direction).max(0.0) / PI,
+            is_specular: false,
+            wavelengths: ray.wavelengths,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::repeat(self.pdf(ray, hit_rec, direction))
+    }
+
+    fn pdf(&self, _ray: &Ray, hit_rec: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        // Light arriving from outside can only be sampled where a walk leaves
+        if hit_rec.front_face {
+            return 0.0;
+        }
+        (-hit_rec.normal).dot(&direction.normalize()).max(0.0) / PI
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,127 @@
+use super::*;
+
+/// Wavelengths the film's reflectance is integrated over for RGB paths, enough to resolve
+/// the interference fringes of films up to about a micrometer thick.
+const FILM_SAMPLES: usize = 16;
+
+/// A thin dielectric coating on top of a surface, whose interference tints the Fresnel
+/// reflectance depending on the wavelength and the angle.
+#[derive(Debug, Clone)]
+pub struct ThinFilm {
+    /// In nanometers
+    pub thickness: f64,
+    pub ir: f64,
+}
+
+impl ThinFilm {
+    /// Reflectance of light arriving from a medium with index of refraction `outside`,
+    /// through the film, onto a substrate with the complex index of refraction returned by
+    /// `substrate` for a wavelength. Exact for a path that carries a single wavelength,
+    /// otherwise integrated to RGB.
+    pub fn reflectance(
+        &self,
+        cos_theta: f64,
+        outside: f64,
+        substrate: impl Fn(f64) -> Complex<f64>,
+        wavelengths: Option<Wavelengths>,
+    ) -> Vector3<f64> {
+        if let Some(wavelengths) = wavelengths.filter(Wavelengths::is_single) {
+            let wavelength = wavelengths.hero();
+            let value = self.reflectance_at(cos_theta, outside, substrate(wavelength), wavelength);
+            return Vector3::repeat(value);
+        }
+        let sum: Vector3<f64> = (0..FILM_SAMPLES)
+            .map(|i| {
+                let t = (i as f64 + 0.5) / FILM_SAMPLES as f64;
+                let wavelength = WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+                let value =
+                    self.reflectance_at(cos_theta, outside, substrate(wavelength), wavelength);
+                wavelength_rgb(wavelength) * value
+            })
+            .sum();
+        sum / FILM_SAMPLES as f64
+    }
+
+    /// The Airy reflectance of the film at a single wavelength in nanometers, averaged over
+    /// both polarizations.
+    pub fn reflectance_at(
+        &self,
+        cos_theta: f64,
+        outside: f64,
+        substrate: Complex<f64>,
+        wavelength: f64,
+    ) -> f64 {
+        let cos0 = Complex::new(cos_theta.clamp(0.0, 1.0), 0.0);
+        let sin0 = (1.0 - cos_theta.clamp(0.0, 1.0).powi(2)).sqrt();
+        let n0 = Complex::new(outside, 0.0);
+        let n1 = Complex::new(self.ir, 0.0);
+        let n2 = substrate;
+        // Snell's law, complex past the critical angle or inside an absorbing substrate
+        let cos_in = |n: Complex<f64>| {
+            let sin = n0 * sin0 / n;
+            (Complex::new(1.0, 0.0) - sin * sin).sqrt()
+        };
+        let (cos1, cos2) = (cos_in(n1), cos_in(n2));
+
+        let phase = Complex::new(0.0, 4.0 * PI * self.thickness / wavelength) * n1 * cos1;
+        let shift = phase.exp();
+        let airy = |r01: Complex<f64>, r12: Complex<f64>| {
+            let r = (r01 + r12 * shift) / (1.0 + r01 * r12 * shift);
+            r.norm_sqr().min(1.0)
+        };
+        let perpendicular = airy(
+            (n0 * cos0 - n1 * cos1) / (n0 * cos0 + n1 * cos1),
+            (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+        );
+        let parallel = airy(
+            (n1 * cos0 - n0 * cos1) / (n1 * cos0 + n0 * cos1),
+            (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2),
+        );
+        0.5 * (perpendicular + parallel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_film_is_plain_fresnel() {
+        let glass = Complex::new(1.5, 0.0);
+        let gold = Conductor::preset("gold").unwrap();
+        let empty = ThinFilm {
+            thickness: 0.0,
+            ir: 1.33,
+        };
+        // A film of the outside medium itself
+        let air = ThinFilm {
+            thickness: 300.0,
+            ir: 1.0,
+        };
+        for cos_theta in [1.0, 0.7, 0.2] {
+            let expected = fresnel_dielectric(cos_theta, 1.5);
+            for film in [&empty, &air] {
+                let reflectance = film.reflectance_at(cos_theta, 1.0, glass, 550.0);
+                assert!((reflectance - expected).abs() < 1e-9, "{:?}", film);
+            }
+            let ir = gold.ir_at(650.0);
+            let expected =
+                fresnel_conductor(cos_theta, &Vector3::repeat(ir.re), &Vector3::repeat(ir.im))[0];
+            let reflectance = empty.reflectance_at(cos_theta, 1.0, ir, 650.0);
+            assert!((reflectance - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn quarter_wave_coating_cancels_reflection() {
+        let ir = 1.5f64.sqrt();
+        let film = ThinFilm {
+            thickness: 550.0 / (4.0 * ir),
+            ir,
+        };
+        let substrate = Complex::new(1.5, 0.0);
+        assert!(film.reflectance_at(1.0, 1.0, substrate, 550.0) < 1e-12);
+        // But not at other wavelengths
+        assert!(film.reflectance_at(1.0, 1.0, substrate, 400.0) > 1e-3);
+    }
+}
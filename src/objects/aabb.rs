@@ -0,0 +1,137 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub minimum: Vector3<f64>,
+    pub maximum: Vector3<f64>,
+}
+
+impl Aabb {
+    pub fn new(minimum: Vector3<f64>, maximum: Vector3<f64>) -> Self {
+        Aabb { minimum, maximum }
+    }
+
+    /// A box that contains nothing, useful as the identity for `surrounding`.
+    pub fn empty() -> Self {
+        Aabb {
+            minimum: Vector3::repeat(f64::INFINITY),
+            maximum: Vector3::repeat(f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            minimum: self.minimum.inf(&other.minimum),
+            maximum: self.maximum.sup(&other.maximum),
+        }
+    }
+
+    pub fn centroid(&self) -> Vector3<f64> {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        (self.maximum - self.minimum).imax()
+    }
+
+    pub fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> bool {
+        self.intersect(ray, t_range).is_some()
+    }
+
+    /// The part of `t_range` in which the ray is inside the box.
+    pub fn intersect(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> Option<(f64, f64)> {
+        let mut t_min = t_range.start;
+        let mut t_max = t_range.end;
+        for a in 0..3 {
+            let inv_d = 1.0 / ray.direction[a];
+            let mut t0 = (self.minimum[a] - ray.origin[a]) * inv_d;
+            let mut t1 = (self.maximum[a] - ray.origin[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // f64::max/min ignore NaN, which happens for rays parallel to a slab face
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
+
+impl<T: Bounded> Bounded for &T {
+    fn bounding_box(&self) -> Aabb {
+        (*self).bounding_box()
+    }
+}
+
+pub trait Bounded {
+    fn bounding_box(&self) -> Aabb;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(vector![-1.0, -1.0, -1.0], vector![1.0, 1.0, 1.0])
+    }
+
+    fn ray(origin: Vector3<f64>, direction: Vector3<f64>) -> Ray {
+        Ray {
+            origin,
+            direction,
+            wavelengths: None,
+        }
+    }
+
+    #[test]
+    fn ray_from_outside() {
+        let ray = ray(vector![0.0, 0.0, 5.0], vector![0.0, 0.0, -1.0]);
+        assert_eq!(
+            unit_box().intersect(&ray, 0.0..f64::INFINITY),
+            Some((4.0, 6.0))
+        );
+        assert_eq!(unit_box().intersect(&ray, 0.0..3.0), None);
+        let away = Ray {
+            direction: vector![0.0, 0.0, 1.0],
+            ..ray
+        };
+        assert_eq!(unit_box().intersect(&away, 0.0..f64::INFINITY), None);
+    }
+
+    #[test]
+    fn ray_from_inside() {
+        let ray = ray(vector![0.0, 0.5, 0.0], vector![0.0, 1.0, 0.0]);
+        assert_eq!(
+            unit_box().intersect(&ray, 0.0..f64::INFINITY),
+            Some((0.0, 0.5))
+        );
+    }
+
+    #[test]
+    fn parallel_ray() {
+        // Inside the slabs of the axes it doesn't move along
+        let inside = ray(vector![0.5, -5.0, 0.5], vector![0.0, 1.0, 0.0]);
+        assert_eq!(
+            unit_box().intersect(&inside, 0.0..f64::INFINITY),
+            Some((4.0, 6.0))
+        );
+        // Outside of them
+        let outside = ray(vector![2.0, -5.0, 0.0], vector![0.0, 1.0, 0.0]);
+        assert_eq!(unit_box().intersect(&outside, 0.0..f64::INFINITY), None);
+        // On a face, where 0 * inf gives NaN
+        let face = ray(vector![1.0, -5.0, 0.0], vector![0.0, 1.0, 0.0]);
+        assert!(unit_box().hit(&face, 0.0..f64::INFINITY));
+    }
+
+    #[test]
+    fn empty_box() {
+        let ray = ray(vector![0.0, 0.0, 0.0], vector![1.0, 1.0, 1.0]);
+        assert_eq!(Aabb::empty().intersect(&ray, 0.0..f64::INFINITY), None);
+        let surrounding = Aabb::empty().surrounding(&unit_box());
+        assert_eq!(surrounding.minimum, unit_box().minimum);
+        assert_eq!(surrounding.maximum, unit_box().maximum);
+    }
+}
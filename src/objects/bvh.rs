@@ -0,0 +1,187 @@
+use super::*;
+
+#[derive(Debug, Clone)]
+pub enum BvhNode<T> {
+    Empty,
+    Leaf(T),
+    Branch {
+        bbox: Aabb,
+        left: Box<BvhNode<T>>,
+        right: Box<BvhNode<T>>,
+    },
+}
+
+impl<T: Bounded> BvhNode<T> {
+    pub fn new(mut objects: Vec<T>) -> Self {
+        match objects.len() {
+            0 => BvhNode::Empty,
+            1 => BvhNode::Leaf(objects.pop().unwrap()),
+            len => {
+                // Split along the axis where the centroids are spread the most
+                let centroid_bounds = objects.iter().fold(Aabb::empty(), |acc, obj| {
+                    let centroid = obj.bounding_box().centroid();
+                    acc.surrounding(&Aabb::new(centroid, centroid))
+                });
+                let axis = centroid_bounds.longest_axis();
+                objects.sort_by(|a, b| {
+                    let a = a.bounding_box().centroid()[axis];
+                    let b = b.bounding_box().centroid()[axis];
+                    // Empty boxes, like those of meshes without faces, have NaN centroids
+                    a.total_cmp(&b)
+                });
+
+                let right_objects = objects.split_off(len / 2);
+                let left = BvhNode::new(objects);
+                let right = BvhNode::new(right_objects);
+                BvhNode::Branch {
+                    bbox: left.bounding_box().surrounding(&right.bounding_box()),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }
+            }
+        }
+    }
+}
+
+impl<T: Bounded> Bounded for BvhNode<T> {
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            BvhNode::Empty => Aabb::empty(),
+            BvhNode::Leaf(obj) => obj.bounding_box(),
+            BvhNode::Branch { bbox, .. } => *bbox,
+        }
+    }
+}
+
+impl<'b, 'a: 'b, T: Hit<'b, 'a>> Hit<'b, 'a> for BvhNode<T> {
+    fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool {
+        match self {
+            BvhNode::Empty => false,
+            BvhNode::Leaf(obj) => obj.hit(ray, t_range, hit_rec),
+            BvhNode::Branch { bbox, left, right } => {
+                if !bbox.hit(ray, t_range.clone()) {
+                    return false;
+                }
+                let hit_left = left.hit(ray, t_range.clone(), hit_rec);
+                let closest = if hit_left { hit_rec.t } else { t_range.end };
+                let hit_right = right.hit(ray, t_range.start..closest, hit_rec);
+                hit_left || hit_right
+            }
+        }
+    }
+
+    fn hit_any(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> bool {
+        match self {
+            BvhNode::Empty => false,
+            BvhNode::Leaf(obj) => obj.hit_any(ray, t_range),
+            BvhNode::Branch { bbox, left, right } => {
+                bbox.hit(ray, t_range.clone())
+                    && (left.hit_any(ray, t_range.clone()) || right.hit_any(ray, t_range))
+            }
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> f64 {
+        match self {
+            BvhNode::Empty => 1.0,
+            BvhNode::Leaf(obj) => obj.transmittance(ray, t_range),
+            BvhNode::Branch { bbox, left, right } => {
+                if !bbox.hit(ray, t_range.clone()) {
+                    return 1.0;
+                }
+                let left = left.transmittance(ray, t_range.clone());
+                if left <= 0.0 {
+                    return 0.0;
+                }
+                left * right.transmittance(ray, t_range)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn random_ray(rng: &mut impl Rng) -> Ray {
+        Ray {
+            origin: Vector3::from_fn(|_, _| rng.gen_range(-12.0..12.0)),
+            direction: Vector3::from_fn(|_, _| rng.gen_range(-1.0..1.0)),
+            wavelengths: None,
+        }
+    }
+
+    #[test]
+    fn hits_match_linear_scan() {
+        let mut rng = rngs::StdRng::seed_from_u64(1);
+        let material = Material::None;
+        let mut objects: Vec<_> = (0..200)
+            .map(|_| {
+                Object::Sphere(
+                    SphereBuilder::default()
+                        .center(Vector3::from_fn(|_, _| rng.gen_range(-10.0..10.0)))
+                        .radius(rng.gen_range(0.1..1.5))
+                        .material(&material)
+                        .build()
+                        .unwrap(),
+                )
+            })
+            .collect();
+        objects.push(Object::Triangle(Triangle::new(
+            [
+                vector![-5.0, -5.0, 0.0],
+                vector![5.0, -5.0, 0.0],
+                vector![0.0, 5.0, 0.0],
+            ],
+            &material,
+        )));
+        let bvh = BvhNode::new(objects.iter().collect());
+        let linear = &objects[..];
+
+        for _ in 0..2000 {
+            let ray = random_ray(&mut rng);
+            let mut bvh_rec = HitRecord::new(&material);
+            let mut linear_rec = HitRecord::new(&material);
+            let bvh_hit = bvh.hit(&ray, 1e-3..f64::INFINITY, &mut bvh_rec);
+            let linear_hit = linear.hit(&ray, 1e-3..f64::INFINITY, &mut linear_rec);
+            assert_eq!(bvh_hit, linear_hit);
+            assert_eq!(bvh.hit_any(&ray, 1e-3..f64::INFINITY), linear_hit);
+            if bvh_hit {
+                assert_eq!(bvh_rec.t, linear_rec.t);
+                assert_eq!(bvh_rec.point, linear_rec.point);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_meshes_dont_panic() {
+        let material = Material::None;
+        let sphere = Object::Sphere(
+            SphereBuilder::default()
+                .material(&material)
+                .build()
+                .unwrap(),
+        );
+        let empty = || {
+            Object::Mesh(
+                MeshBuilder::default()
+                    .data(Arc::new(MeshData::default()))
+                    .material(&material)
+                    .build()
+                    .unwrap(),
+            )
+        };
+        let objects = [empty(), sphere, empty()];
+        let bvh = BvhNode::new(objects.iter().collect());
+
+        let ray = Ray {
+            origin: vector![0.0, 0.0, 5.0],
+            direction: vector![0.0, 0.0, -1.0],
+            wavelengths: None,
+        };
+        let mut hit_rec = HitRecord::new(&material);
+        assert!(bvh.hit(&ray, 1e-3..f64::INFINITY, &mut hit_rec));
+        assert!((hit_rec.t - 4.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,150 @@
+use super::*;
+use std::path::Path;
+
+/// A dense voxel grid of densities over the unit cube, with x varying fastest, then y, then z.
+#[derive(Debug, Clone)]
+pub struct DensityGrid {
+    pub resolution: [usize; 3],
+    data: Vec<f32>,
+    max: f64,
+}
+
+impl DensityGrid {
+    pub fn new(resolution: [usize; 3], data: Vec<f32>) -> Self {
+        assert_eq!(
+            data.len(),
+            resolution.iter().product::<usize>(),
+            "Grid data doesn't match its resolution"
+        );
+        let max = data.iter().fold(0.0f32, |max, x| max.max(*x)) as f64;
+        DensityGrid {
+            resolution,
+            data,
+            max,
+        }
+    }
+
+    /// Reads a headerless file of little-endian 32 bit floats in the order of `new`.
+    pub fn load_raw(path: impl AsRef<Path>, resolution: [usize; 3]) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let expected = 4 * resolution.iter().product::<usize>();
+        if bytes.len() != expected {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} bytes for a {}x{}x{} grid, found {}",
+                    expected,
+                    resolution[0],
+                    resolution[1],
+                    resolution[2],
+                    bytes.len()
+                ),
+            ));
+        }
+        let data = bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]).max(0.0))
+            .collect();
+        Ok(DensityGrid::new(resolution, data))
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.data[(z * ny + y) * nx + x] as f64
+    }
+
+    /// Trilinear interpolation between voxel centers, `local` in [0, 1]³.
+    pub fn lookup(&self, local: &Vector3<f64>) -> f64 {
+        let mut cells = [(0, 0, 0.0); 3];
+        for (axis, cell) in cells.iter_mut().enumerate() {
+            let size = self.resolution[axis];
+            let x = (local[axis] * size as f64 - 0.5).clamp(0.0, (size - 1) as f64);
+            let low = (x as usize).min(size.saturating_sub(2));
+            let high = (low + 1).min(size - 1);
+            *cell = (low, high, x - low as f64);
+        }
+        let [(x0, x1, tx), (y0, y1, ty), (z0, z1, tz)] = cells;
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let plane = |z| {
+            lerp(
+                lerp(self.voxel(x0, y0, z), self.voxel(x1, y0, z), tx),
+                lerp(self.voxel(x0, y1, z), self.voxel(x1, y1, z), tx),
+                ty,
+            )
+        };
+        lerp(plane(z0), plane(z1), tz)
+    }
+}
+
+/// Where the density of a heterogeneous medium comes from.
+#[derive(Debug, Clone)]
+pub enum DensityField {
+    /// Stretched over the bounds of the medium
+    Grid(DensityGrid),
+    /// The average of the noise texture's color, in world space
+    Noise(Noise),
+}
+
+impl DensityField {
+    /// `point` in world space, `local` the same point in [0, 1]³ over the bounds.
+    pub fn density(&self, point: &Vector3<f64>, local: &Vector3<f64>) -> f64 {
+        match self {
+            DensityField::Grid(grid) => grid.lookup(local),
+            DensityField::Noise(noise) => {
+                let t = noise.blend(point);
+                ((1.0 - t) * noise.low + t * noise.high).mean().max(0.0)
+            }
+        }
+    }
+
+    /// An upper bound of `density`, the majorant for delta tracking.
+    pub fn max(&self) -> f64 {
+        match self {
+            DensityField::Grid(grid) => grid.max,
+            DensityField::Noise(noise) => noise.low.mean().max(noise.high.mean()).max(0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trilinear_between_voxel_centers() {
+        // The voxel index as its density
+        let grid = DensityGrid::new([2, 2, 2], (0..8).map(|i| i as f32).collect());
+        assert_eq!(grid.lookup(&vector![0.25, 0.25, 0.25]), 0.0);
+        assert_eq!(grid.lookup(&vector![0.75, 0.75, 0.75]), 7.0);
+        assert_eq!(grid.lookup(&vector![0.75, 0.25, 0.25]), 1.0);
+        assert_eq!(grid.lookup(&vector![0.25, 0.75, 0.25]), 2.0);
+        assert_eq!(grid.lookup(&vector![0.25, 0.25, 0.75]), 4.0);
+        assert_eq!(grid.lookup(&vector![0.5, 0.5, 0.5]), 3.5);
+        // Clamped to the outer centers
+        assert_eq!(grid.lookup(&vector![0.0, 0.0, 1.0]), 4.0);
+        // A single voxel along an axis
+        let slab = DensityGrid::new([2, 1, 1], vec![1.0, 3.0]);
+        assert_eq!(slab.lookup(&vector![0.5, 0.9, 0.1]), 2.0);
+    }
+
+    #[test]
+    fn raw_grids() {
+        let path = std::env::temp_dir().join("rustyray-grid.raw");
+        let bytes: Vec<u8> = [0.5f32, -1.0, 2.0]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        std::fs::write(&path, bytes).unwrap();
+        let grid = DensityGrid::load_raw(&path, [3, 1, 1]);
+        let wrong = DensityGrid::load_raw(&path, [2, 2, 1]);
+        std::fs::remove_file(&path).unwrap();
+        let grid = grid.unwrap();
+        // Negative densities are clamped
+        assert_eq!(grid.data, vec![0.5, 0.0, 2.0]);
+        assert_eq!(grid.max, 2.0);
+        assert_eq!(
+            wrong.unwrap_err().to_string(),
+            "expected 16 bytes for a 2x2x1 grid, found 12"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use super::*;
+use std::sync::Arc;
+
+/// A participating medium of constant density filling a closed, convex `boundary`. Rays
+/// scatter off it at distances sampled from the exponential free-flight distribution, with
+/// the phase function of `material`.
+#[derive(Debug, Clone)]
+pub struct ConstantMedium<'a> {
+    pub boundary: Box<Object<'a>>,
+    /// Chance of scattering per unit length
+    pub density: f64,
+    pub material: &'a Material,
+}
+
+impl<'a> ConstantMedium<'a> {
+    pub fn new(boundary: Object<'a>, density: f64, material: &'a Material) -> Self {
+        ConstantMedium {
+            boundary: Box::new(boundary),
+            density,
+            material,
+        }
+    }
+
+    /// The part of `t_range` in which the ray is inside the boundary.
+    fn inside(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> Option<(f64, f64)> {
+        // Where the whole line enters and leaves, the ray may start inside
+        let mut entry = HitRecord::new(self.material);
+        if !self
+            .boundary
+            .hit(ray, f64::NEG_INFINITY..f64::INFINITY, &mut entry)
+        {
+            return None;
+        }
+        let mut exit = HitRecord::new(self.material);
+        if !self
+            .boundary
+            .hit(ray, entry.t + 1e-4..f64::INFINITY, &mut exit)
+        {
+            return None;
+        }
+        let start = entry.t.max(t_range.start);
+        let end = exit.t.min(t_range.end);
+        (start < end).then_some((start, end))
+    }
+}
+
+impl<'b, 'a: 'b> Hit<'b, 'a> for ConstantMedium<'a> {
+    fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool {
+        let (start, end) = match self.inside(ray, t_range) {
+            Some(inside) => inside,
+            None => return false,
+        };
+        let speed = ray.direction.norm();
+        let distance = free_flight(self.density);
+        if distance > (end - start) * speed {
+            return false;
+        }
+        scatter_at(ray, start + distance / speed, self.material, hit_rec);
+        true
+    }
+
+    fn transmittance(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> f64 {
+        match self.inside(ray, t_range) {
+            Some((start, end)) => (-self.density * (end - start) * ray.direction.norm()).exp(),
+            None => 1.0,
+        }
+    }
+}
+
+impl Bounded for ConstantMedium<'_> {
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+/// A participating medium whose density varies over its `bounds`, sampled with delta
+/// tracking against the largest density and attenuating shadow rays with ratio tracking.
+#[derive(Debug, Clone)]
+pub struct HeterogeneousMedium<'a> {
+    pub bounds: Aabb,
+    pub field: Arc<DensityField>,
+    /// Scales the densities of the field
+    pub density: f64,
+    pub material: &'a Material,
+    majorant: f64,
+}
+
+impl<'a> HeterogeneousMedium<'a> {
+    pub fn new(
+        bounds: Aabb,
+        field: Arc<DensityField>,
+        density: f64,
+        material: &'a Material,
+    ) -> Self {
+        let majorant = field.max() * density;
+        HeterogeneousMedium {
+            bounds,
+            field,
+            density,
+            material,
+            majorant,
+        }
+    }
+
+    pub fn density_at(&self, point: &Vector3<f64>) -> f64 {
+        let local = (point - self.bounds.minimum)
+            .component_div(&(self.bounds.maximum - self.bounds.minimum));
+        self.density * self.field.density(point, &local)
+    }
+
+    /// Steps through the bounds with tentative collisions against the majorant, calling
+    /// `collide` at each until it returns false. Returns the parameter of the last collision.
+    fn track(
+        &self,
+        ray: &Ray,
+        t_range: std::ops::Range<f64>,
+        mut collide: impl FnMut(f64) -> bool,
+    ) -> Option<f64> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+        let (mut t, end) = self.bounds.intersect(ray, t_range)?;
+        let speed = ray.direction.norm();
+        loop {
+            t += free_flight(self.majorant) / speed;
+            if t >= end {
+                return None;
+            }
+            if !collide(t) {
+                return Some(t);
+            }
+        }
+    }
+}
+
+impl<'b, 'a: 'b> Hit<'b, 'a> for HeterogeneousMedium<'a> {
+    fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool {
+        // Delta tracking, a collision is real with the probability density / majorant
+        let mut rng = render_rng();
+        let collision = self.track(ray, t_range, |t| {
+            rng.gen::<f64>() * self.majorant >= self.density_at(&ray.at(t))
+        });
+        match collision {
+            Some(t) => {
+                scatter_at(ray, t, self.material, hit_rec);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> f64 {
+        // Ratio tracking, every tentative collision lets through the null collision fraction
+        let mut transmittance = 1.0;
+        self.track(ray, t_range, |t| {
+            transmittance *= 1.0 - self.density_at(&ray.at(t)) / self.majorant;
+            transmittance > 0.0
+        });
+        transmittance.max(0.0)
+    }
+}
+
+impl Bounded for HeterogeneousMedium<'_> {
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+/// A distance to the next collision in a medium of `density`.
+fn free_flight(density: f64) -> f64 {
+    -(1.0 - render_rng().gen::<f64>()).ln() / density
+}
+
+/// Fills in `hit_rec` for a ray scattering inside a medium at `t`.
+fn scatter_at<'b>(ray: &Ray, t: f64, material: &'b Material, hit_rec: &mut HitRecord<'b>) {
+    hit_rec.t = t;
+    hit_rec.point = ray.at(t);
+    // There is no surface, any normal will do
+    hit_rec.normal = -ray.direction.normalize();
+    hit_rec.front_face = true;
+    hit_rec.uv = Vector2::zeros();
+    hit_rec.tangent = Vector3::zeros();
+    hit_rec.bitangent = Vector3::zeros();
+    hit_rec.material = material;
+    hit_rec.area = 0.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fog() -> Material {
+        Material::Isotropic(IsotropicBuilder::default().build().unwrap())
+    }
+
+    #[test]
+    fn constant_medium_transmittance() {
+        let fog = fog();
+        let sphere = SphereBuilder::default()
+            .radius(1.0)
+            .material(&fog)
+            .build()
+            .unwrap();
+        let medium = ConstantMedium::new(Object::Sphere(sphere), 0.5, &fog);
+        let ray = Ray {
+            origin: vector![0.0, 0.0, -3.0],
+            direction: vector![0.0, 0.0, 2.0],
+            wavelengths: None,
+        };
+        // Through the whole diameter, from the center and missing it
+        let through = medium.transmittance(&ray, 0.0..f64::INFINITY);
+        assert!((through - (-0.5f64 * 2.0).exp()).abs() < 1e-12);
+        let from_center = medium.transmittance(&ray, 1.5..f64::INFINITY);
+        assert!((from_center - (-0.5f64).exp()).abs() < 1e-12);
+        assert_eq!(medium.transmittance(&ray, 0.0..0.5), 1.0);
+
+        // The fraction of rays passing without scattering
+        seed_render_rng(11);
+        let mut hit_rec = HitRecord::new(&fog);
+        let count = 20000;
+        let passed = (0..count)
+            .filter(|_| !medium.hit(&ray, 0.0..f64::INFINITY, &mut hit_rec))
+            .count();
+        let fraction = passed as f64 / count as f64;
+        assert!(
+            (fraction - through).abs() < 0.01,
+            "{} {}",
+            fraction,
+            through
+        );
+    }
+
+    #[test]
+    fn tracking_a_constant_grid() {
+        let fog = fog();
+        let field = DensityField::Grid(DensityGrid::new([2, 2, 2], vec![0.5; 8]));
+        let bounds = Aabb::new(vector![-1.0, -1.0, -1.0], vector![1.0, 1.0, 1.0]);
+        let medium = HeterogeneousMedium::new(bounds, Arc::new(field), 2.0, &fog);
+        let ray = Ray {
+            origin: vector![0.0, 0.0, -3.0],
+            direction: vector![0.0, 0.0, 1.0],
+            wavelengths: None,
+        };
+        // A density of 1 along 2 units
+        let expected = (-2.0f64).exp();
+        seed_render_rng(13);
+        let count = 20000;
+        let mut hit_rec = HitRecord::new(&fog);
+        let passed = (0..count)
+            .filter(|_| !medium.hit(&ray, 0.0..f64::INFINITY, &mut hit_rec))
+            .count();
+        let fraction = passed as f64 / count as f64;
+        assert!(
+            (fraction - expected).abs() < 0.01,
+            "{} {}",
+            fraction,
+            expected
+        );
+        // Every tentative collision is real against a tight majorant, so ratio tracking is
+        // either 0 or 1 and averages out the same
+        let mean = (0..count)
+            .map(|_| medium.transmittance(&ray, 0.0..f64::INFINITY))
+            .sum::<f64>()
+            / count as f64;
+        assert!((mean - expected).abs() < 0.01, "{} {}", mean, expected);
+    }
+
+    #[test]
+    fn ratio_tracking_a_loose_majorant() {
+        let fog = fog();
+        // Every other voxel along x is empty
+        let data = (0..8).map(|i| if i % 2 == 0 { 0.0 } else { 1.0 }).collect();
+        let field = DensityField::Grid(DensityGrid::new([2, 2, 2], data));
+        let bounds = Aabb::new(vector![0.0, 0.0, 0.0], vector![1.0, 1.0, 1.0]);
+        let medium = HeterogeneousMedium::new(bounds, Arc::new(field), 1.0, &fog);
+        let ray = Ray {
+            origin: vector![0.5, 0.5, -1.0],
+            direction: vector![0.0, 0.0, 1.0],
+            wavelengths: None,
+        };
+        // Halfway between the empty and the full voxels, at half the majorant
+        let expected = (-0.5f64).exp();
+        seed_render_rng(17);
+        let count = 20000;
+        let mean = (0..count)
+            .map(|_| medium.transmittance(&ray, 0.0..f64::INFINITY))
+            .sum::<f64>()
+            / count as f64;
+        assert!((mean - expected).abs() < 0.01, "{} {}", mean, expected);
+    }
+}
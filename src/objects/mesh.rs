@@ -0,0 +1,390 @@
+use super::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Vertex buffers shared by all the triangles of a mesh.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Vector3<f64>>,
+    pub normals: Vec<Vector3<f64>>,
+    pub uvs: Vec<Vector2<f64>>,
+    pub faces: Vec<Face>,
+}
+
+impl MeshData {
+    pub fn face_vertices(&self, face: usize) -> [Vector3<f64>; 3] {
+        let indices = self.faces[face].positions;
+        [
+            self.positions[indices[0]],
+            self.positions[indices[1]],
+            self.positions[indices[2]],
+        ]
+    }
+
+    /// Uniformly sample a point on `face`, the pdf is left to the caller.
+    fn sample_face(&self, face: usize) -> (Vector3<f64>, Vector3<f64>) {
+        let [v0, v1, v2] = self.face_vertices(face);
+        let mut rng = render_rng();
+        let su = rng.gen::<f64>().sqrt();
+        let b1 = rng.gen::<f64>() * su;
+        let b0 = 1.0 - su;
+        let point = b0 * v0 + b1 * v1 + (1.0 - b0 - b1) * v2;
+        let normal = (v1 - v0).cross(&(v2 - v0)).normalize();
+        (point, normal)
+    }
+
+    pub fn face_area(&self, face: usize) -> f64 {
+        let [v0, v1, v2] = self.face_vertices(face);
+        0.5 * (v1 - v0).cross(&(v2 - v0)).norm()
+    }
+}
+
+/// Whether the faces of `meshes` together enclose a volume, which is the case if every edge
+/// is shared by exactly two faces. Corners are matched by position, so a surface split into
+/// several meshes still counts as one.
+pub fn is_closed<'m>(meshes: impl IntoIterator<Item = &'m MeshData>) -> bool {
+    let mut edges: HashMap<_, u32> = HashMap::new();
+    for data in meshes {
+        for face in 0..data.faces.len() {
+            let corners = data
+                .face_vertices(face)
+                .map(|v| [v.x, v.y, v.z].map(f64::to_bits));
+            for k in 0..3 {
+                let (a, b) = (corners[k], corners[(k + 1) % 3]);
+                *edges
+                    .entry(if a < b { (a, b) } else { (b, a) })
+                    .or_default() += 1;
+            }
+        }
+    }
+    !edges.is_empty() && edges.values().all(|&count| count == 2)
+}
+
+/// Indices into the `MeshData` buffers for the three corners of a triangle.
+#[derive(Debug, Clone, Copy)]
+pub struct Face {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Triangle<'a> {
+    pub mesh: Arc<MeshData>,
+    pub face: usize,
+    pub material: &'a Material,
+}
+
+impl<'a> Triangle<'a> {
+    /// A standalone triangle with flat shading.
+    pub fn new(vertices: [Vector3<f64>; 3], material: &'a Material) -> Self {
+        let mesh = MeshData {
+            positions: vertices.to_vec(),
+            faces: vec![Face {
+                positions: [0, 1, 2],
+                normals: None,
+                uvs: None,
+            }],
+            ..Default::default()
+        };
+        Triangle {
+            mesh: Arc::new(mesh),
+            face: 0,
+            material,
+        }
+    }
+
+    pub fn vertices(&self) -> [Vector3<f64>; 3] {
+        self.mesh.face_vertices(self.face)
+    }
+}
+
+impl<'b, 'a: 'b> Hit<'b, 'a> for Triangle<'a> {
+    fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool {
+        // Möller–Trumbore
+        let [v0, v1, v2] = self.vertices();
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let p = ray.direction.cross(&edge2);
+        let det = edge1.dot(&p);
+        if det.abs() < f64::EPSILON {
+            return false;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = ray.origin - v0;
+        let u = s.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return false;
+        }
+        let q = s.cross(&edge1);
+        let v = ray.direction.dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return false;
+        }
+        let t = edge2.dot(&q) * inv_det;
+        if !t_range.contains(&t) {
+            return false;
+        }
+
+        let cross = edge1.cross(&edge2);
+        let mut geometric_normal = cross.normalize();
+        let face = &self.mesh.faces[self.face];
+        let shading_normal = match face.normals {
+            Some(indices) => {
+                let normal = ((1.0 - u - v) * self.mesh.normals[indices[0]]
+                    + u * self.mesh.normals[indices[1]]
+                    + v * self.mesh.normals[indices[2]])
+                    .normalize();
+                // Trust the vertex normals over the winding order
+                if geometric_normal.dot(&normal) < 0.0 {
+                    geometric_normal = -geometric_normal;
+                }
+                normal
+            }
+            None => geometric_normal,
+        };
+
+        hit_rec.t = t;
+        hit_rec.point = ray.at(t);
+        match face.uvs {
+            Some(indices) => {
+                let [uv0, uv1, uv2] = indices.map(|i| self.mesh.uvs[i]);
+                hit_rec.uv = (1.0 - u - v) * uv0 + u * uv1 + v * uv2;
+                let (duv1, duv2) = (uv1 - uv0, uv2 - uv0);
+                let uv_det = duv1[0] * duv2[1] - duv1[1] * duv2[0];
+                if uv_det.abs() > f64::EPSILON {
+                    hit_rec.tangent = (duv2[1] * edge1 - duv1[1] * edge2) / uv_det;
+                    hit_rec.bitangent = (duv1[0] * edge2 - duv2[0] * edge1) / uv_det;
+                } else {
+                    hit_rec.tangent = Vector3::zeros();
+                    hit_rec.bitangent = Vector3::zeros();
+                }
+            }
+            // Barycentric coordinates
+            None => {
+                hit_rec.uv = vector![u, v];
+                hit_rec.tangent = edge1;
+                hit_rec.bitangent = edge2;
+            }
+        }
+        hit_rec.set_face_normal(ray, geometric_normal);
+        hit_rec.normal = if hit_rec.front_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+        hit_rec.material = self.material;
+        hit_rec.area = 0.5 * cross.norm();
+
+        true
+    }
+}
+
+impl Bounded for Triangle<'_> {
+    fn bounding_box(&self) -> Aabb {
+        let [v0, v1, v2] = self.vertices();
+        // Pad so axis-aligned triangles don't produce flat boxes
+        let padding = Vector3::repeat(1e-8);
+        Aabb::new(
+            v0.inf(&v1).inf(&v2) - padding,
+            v0.sup(&v1).sup(&v2) + padding,
+        )
+    }
+}
+
+impl SampleArea for Triangle<'_> {
+    fn sample_area(&self) -> Option<AreaSample> {
+        let area = self.mesh.face_area(self.face);
+        if area <= 0.0 {
+            return None;
+        }
+        let (point, normal) = self.mesh.sample_face(self.face);
+        Some(AreaSample {
+            point,
+            normal,
+            pdf: 1.0 / area,
+        })
+    }
+}
+
+#[derive(Builder, Debug, Clone)]
+#[builder(build_fn(skip))]
+pub struct Mesh<'a> {
+    #[builder(setter(into))]
+    pub data: Arc<MeshData>,
+    pub material: &'a Material,
+
+    #[builder(setter(skip))]
+    bvh: BvhNode<Triangle<'a>>,
+    /// Cumulative face areas, for sampling faces proportionally to their area
+    #[builder(setter(skip))]
+    area_cdf: Vec<f64>,
+}
+
+impl<'b, 'a: 'b> Hit<'b, 'a> for Mesh<'a> {
+    fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool {
+        if self.bvh.hit(ray, t_range, hit_rec) {
+            // Lights are sampled over the whole mesh
+            hit_rec.area = *self.area_cdf.last().unwrap_or(&0.0);
+            return true;
+        }
+        false
+    }
+
+    fn hit_any(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> bool {
+        self.bvh.hit_any(ray, t_range)
+    }
+}
+
+impl SampleArea for Mesh<'_> {
+    fn sample_area(&self) -> Option<AreaSample> {
+        let total_area = *self.area_cdf.last()?;
+        if total_area <= 0.0 {
+            return None;
+        }
+        let target = render_rng().gen::<f64>() * total_area;
+        let face = self
+            .area_cdf
+            .partition_point(|&area| area < target)
+            .min(self.area_cdf.len() - 1);
+        let (point, normal) = self.data.sample_face(face);
+        Some(AreaSample {
+            point,
+            normal,
+            pdf: 1.0 / total_area,
+        })
+    }
+}
+
+impl Bounded for Mesh<'_> {
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+impl<'a> MeshBuilder<'a> {
+    pub fn build(&self) -> Result<Mesh<'a>, MeshBuilderError> {
+        let data = match self.data {
+            Some(ref value) => value.clone(),
+            None => return Result::Err(Into::into(UninitializedFieldError::from("data"))),
+        };
+        let material = match self.material {
+            Some(value) => value,
+            None => return Result::Err(Into::into(UninitializedFieldError::from("material"))),
+        };
+
+        let triangles = (0..data.faces.len())
+            .map(|face| Triangle {
+                mesh: data.clone(),
+                face,
+                material,
+            })
+            .collect();
+        let bvh = BvhNode::new(triangles);
+        let area_cdf = (0..data.faces.len())
+            .scan(0.0, |total, face| {
+                *total += data.face_area(face);
+                Some(*total)
+            })
+            .collect();
+
+        Ok(Mesh {
+            data,
+            material,
+            bvh,
+            area_cdf,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(data: MeshData, material: &Material) -> Mesh<'_> {
+        MeshBuilder::default()
+            .data(data)
+            .material(material)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn empty_mesh_has_no_area_to_sample() {
+        let material = Material::None;
+        assert!(mesh(MeshData::default(), &material).sample_area().is_none());
+    }
+
+    #[test]
+    fn degenerate_triangle_has_no_area_to_sample() {
+        let material = Material::None;
+        let vertices = [
+            vector![0.0, 0.0, 0.0],
+            vector![1.0, 1.0, 1.0],
+            vector![2.0, 2.0, 2.0],
+        ];
+        assert!(Triangle::new(vertices, &material).sample_area().is_none());
+        let vertices = [vertices[0], vertices[1], vector![0.0, 1.0, 0.0]];
+        assert!(Triangle::new(vertices, &material).sample_area().is_some());
+    }
+
+    #[test]
+    fn tetrahedron_is_closed() {
+        let face = |positions| Face {
+            positions,
+            normals: None,
+            uvs: None,
+        };
+        let positions = vec![
+            vector![0.0, 0.0, 0.0],
+            vector![1.0, 0.0, 0.0],
+            vector![0.0, 1.0, 0.0],
+            vector![0.0, 0.0, 1.0],
+        ];
+        let sides = MeshData {
+            positions: positions.clone(),
+            faces: vec![face([0, 2, 1]), face([0, 1, 3]), face([0, 3, 2])],
+            ..Default::default()
+        };
+        assert!(!is_closed([&sides]));
+        // The missing face from a mesh of its own, with its own vertex buffer
+        let lid = MeshData {
+            positions: positions[1..].to_vec(),
+            faces: vec![face([0, 1, 2])],
+            ..Default::default()
+        };
+        assert!(is_closed([&sides, &lid]));
+        assert!(!is_closed([&MeshData::default()]));
+    }
+
+    #[test]
+    fn samples_the_whole_area() {
+        let material = Material::None;
+        let face = |positions| Face {
+            positions,
+            normals: None,
+            uvs: None,
+        };
+        // A 2 by 1 rectangle in the xy plane, split unevenly
+        let data = MeshData {
+            positions: vec![
+                vector![0.0, 0.0, 0.0],
+                vector![2.0, 0.0, 0.0],
+                vector![2.0, 1.0, 0.0],
+                vector![0.0, 1.0, 0.0],
+            ],
+            faces: vec![face([0, 1, 2]), face([0, 2, 3])],
+            ..Default::default()
+        };
+        let mesh = mesh(data, &material);
+        for _ in 0..1000 {
+            let sample = mesh.sample_area().unwrap();
+            assert!((sample.pdf - 0.5).abs() < 1e-12);
+            assert!((0.0..=2.0).contains(&sample.point[0]));
+            assert!((0.0..=1.0).contains(&sample.point[1]));
+            assert_eq!(sample.point[2], 0.0);
+            assert_eq!(sample.normal.abs(), vector![0.0, 0.0, 1.0]);
+        }
+    }
+}
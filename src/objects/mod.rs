@@ -1,14 +1,96 @@
 use super::*;
 
+mod aabb;
+pub use aabb::*;
+
+mod bvh;
+pub use bvh::*;
+
+mod density;
+pub use density::*;
+
+mod medium;
+pub use medium::*;
+
+mod mesh;
+pub use mesh::*;
+
 #[derive(Debug, Clone)]
 pub enum Object<'a> {
     Sphere(Sphere<'a>),
+    Triangle(Triangle<'a>),
+    Mesh(Mesh<'a>),
+    Medium(ConstantMedium<'a>),
+    Volume(HeterogeneousMedium<'a>),
 }
 
 impl<'b, 'a: 'b> Hit<'b, 'a> for Object<'a> {
     fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool {
         match self {
             Object::Sphere(sphere) => sphere.hit(ray, t_range, hit_rec),
+            Object::Triangle(triangle) => triangle.hit(ray, t_range, hit_rec),
+            Object::Mesh(mesh) => mesh.hit(ray, t_range, hit_rec),
+            Object::Medium(medium) => medium.hit(ray, t_range, hit_rec),
+            Object::Volume(volume) => volume.hit(ray, t_range, hit_rec),
+        }
+    }
+
+    fn hit_any(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> bool {
+        match self {
+            Object::Mesh(mesh) => mesh.hit_any(ray, t_range),
+            _ => {
+                let mut hit_rec = HitRecord::new(&Material::None);
+                self.hit(ray, t_range, &mut hit_rec)
+            }
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> f64 {
+        match self {
+            Object::Medium(medium) => medium.transmittance(ray, t_range),
+            Object::Volume(volume) => volume.transmittance(ray, t_range),
+            _ => {
+                if self.hit_any(ray, t_range) {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+impl Object<'_> {
+    pub fn material(&self) -> &Material {
+        match self {
+            Object::Sphere(sphere) => sphere.material,
+            Object::Triangle(triangle) => triangle.material,
+            Object::Mesh(mesh) => mesh.material,
+            Object::Medium(medium) => medium.material,
+            Object::Volume(volume) => volume.material,
+        }
+    }
+}
+
+impl SampleArea for Object<'_> {
+    fn sample_area(&self) -> Option<AreaSample> {
+        match self {
+            Object::Sphere(sphere) => sphere.sample_area(),
+            Object::Triangle(triangle) => triangle.sample_area(),
+            Object::Mesh(mesh) => mesh.sample_area(),
+            Object::Medium(_) | Object::Volume(_) => unreachable!("Media can't be lights"),
+        }
+    }
+}
+
+impl Bounded for Object<'_> {
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            Object::Sphere(sphere) => sphere.bounding_box(),
+            Object::Triangle(triangle) => triangle.bounding_box(),
+            Object::Mesh(mesh) => mesh.bounding_box(),
+            Object::Medium(medium) => medium.bounding_box(),
+            Object::Volume(volume) => volume.bounding_box(),
         }
     }
 }
@@ -47,12 +129,57 @@ impl<'b, 'a: 'b> Hit<'b, 'a> for Sphere<'a> {
         hit_rec.point = ray.at(hit_rec.t);
         let outward_normal = (hit_rec.point - self.center) / self.radius;
         hit_rec.set_face_normal(ray, outward_normal);
+        hit_rec.uv = Sphere::uv(&outward_normal);
+        (hit_rec.tangent, hit_rec.bitangent) = self.tangents(&outward_normal);
         hit_rec.material = self.material;
+        hit_rec.area = 4.0 * std::f64::consts::PI * self.radius.powi(2);
 
         true
     }
 }
 
+impl Sphere<'_> {
+    /// Latitude-longitude coordinates of a point on the unit sphere, v goes from -Y to +Y.
+    fn uv(point: &Vector3<f64>) -> Vector2<f64> {
+        use std::f64::consts::PI;
+        let theta = (-point[1]).clamp(-1.0, 1.0).acos();
+        let phi = (-point[2]).atan2(point[0]) + PI;
+        vector![phi / (2.0 * PI), theta / PI]
+    }
+
+    /// ∂p/∂u and ∂p/∂v for the parametrization of `uv`, zero ∂p/∂v at the poles.
+    fn tangents(&self, normal: &Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+        use std::f64::consts::PI;
+        let [x, y, z] = [normal[0], normal[1], normal[2]];
+        let tangent = 2.0 * PI * self.radius * vector![z, 0.0, -x];
+        let sin_theta = x.hypot(z);
+        let bitangent = if sin_theta > 0.0 {
+            PI * self.radius * vector![-x * y / sin_theta, sin_theta, -y * z / sin_theta]
+        } else {
+            Vector3::zeros()
+        };
+        (tangent, bitangent)
+    }
+}
+
+impl Bounded for Sphere<'_> {
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vector3::repeat(self.radius.abs());
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+}
+
+impl SampleArea for Sphere<'_> {
+    fn sample_area(&self) -> Option<AreaSample> {
+        let normal = random_in_unit_sphere().normalize();
+        Some(AreaSample {
+            point: self.center + self.radius * normal,
+            normal,
+            pdf: 1.0 / (4.0 * std::f64::consts::PI * self.radius.powi(2)),
+        })
+    }
+}
+
 impl<'b, 'a: 'b, T: Hit<'b, 'a>> Hit<'b, 'a> for &[T] {
     fn hit<'c>(
         &self,
@@ -74,14 +201,62 @@ impl<'b, 'a: 'b, T: Hit<'b, 'a>> Hit<'b, 'a> for &[T] {
         }
         hit_anything
     }
+
+    fn hit_any(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> bool {
+        self.iter().any(|obj| obj.hit_any(ray, t_range.clone()))
+    }
+
+    fn transmittance(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> f64 {
+        self.iter()
+            .map(|obj| obj.transmittance(ray, t_range.clone()))
+            .product()
+    }
 }
 
 impl<'b, 'a: 'b, T: Hit<'b, 'a>> Hit<'b, 'a> for &'_ T {
     fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool {
         (*self).hit(ray, t_range, hit_rec)
     }
+
+    fn hit_any(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> bool {
+        (*self).hit_any(ray, t_range)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> f64 {
+        (*self).transmittance(ray, t_range)
+    }
 }
 
 pub trait Hit<'b, 'a: 'b> {
     fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool;
+
+    /// Whether anything is hit at all, for shadow rays. Can stop at the first hit found.
+    fn hit_any(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> bool {
+        let mut hit_rec = HitRecord::new(&Material::None);
+        self.hit(ray, t_range, &mut hit_rec)
+    }
+
+    /// The fraction of light passing along the ray, for shadow rays. Surfaces block it
+    /// entirely, media let part of it through.
+    fn transmittance(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> f64 {
+        if self.hit_any(ray, t_range) {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A point sampled on the surface of an object.
+#[derive(Debug, Clone)]
+pub struct AreaSample {
+    pub point: Vector3<f64>,
+    pub normal: Vector3<f64>,
+    /// With respect to surface area
+    pub pdf: f64,
+}
+
+pub trait SampleArea {
+    /// `None` for objects without any surface to sample, like a mesh without faces.
+    fn sample_area(&self) -> Option<AreaSample>;
 }
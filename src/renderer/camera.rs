@@ -1,21 +1,21 @@
 use super::*;
 
 #[derive(Builder, Debug, Clone)]
-#[builder(build_fn(skip))]
+#[builder(build_fn(skip), derive(Debug))]
 pub struct Camera {
     pub origin: Vector3<f64>,
-    look_at: Vector3<f64>,
-    vup: Vector3<f64>,
+    pub look_at: Vector3<f64>,
+    pub vup: Vector3<f64>,
 
-    vfov: f64, // Vertical field of view (in degrees)
+    pub vfov: f64, // Vertical field of view (in degrees)
     pub aspect_ratio: f64,
-    focal_length: f64,
+    pub focal_length: f64,
     #[builder(setter(skip))]
     pub view_height: f64,
     #[builder(setter(skip))]
     pub view_width: f64,
 
-    aperture: f64,
+    pub aperture: f64,
     pub focus_dist: f64,
     #[builder(setter(skip))]
     lens_radius: f64,
@@ -46,6 +46,7 @@ impl Camera {
                 - t * self.vertical
                 - self.origin
                 - offset),
+            wavelengths: None,
         }
     }
 }
@@ -58,38 +59,14 @@ impl Default for Camera {
 
 impl CameraBuilder {
     pub fn build(&self) -> Result<Camera, CameraBuilderError> {
-        let origin = match self.origin {
-            Some( value) => value,
-            None => vector![0.0, 0.0, 0.0],
-        };
-        let look_at = match self.look_at {
-            Some( value) => value,
-            None => vector![0.0, 0.0, -1.0],
-        };
-        let vup = match self.vup {
-            Some( value) => value,
-            None => vector![0.0, 1.0, 0.0],
-        };
-        let vfov = match self.vfov {
-            Some( value) => value,
-            None => 90.0,
-        };
-        let aspect_ratio = match self.aspect_ratio {
-            Some( value) => value,
-            None => 16.0 / 9.0,
-        };
-        let focal_length = match self.focal_length {
-            Some( value) => value,
-            None => 1.0,
-        };
-        let aperture = match self.aperture {
-            Some( value) => value,
-            None => 2.0,
-        };
-        let focus_dist = match self.focus_dist {
-            Some( value) => value,
-            None => 1.0,
-        };
+        let origin = self.origin.unwrap_or(vector![0.0, 0.0, 0.0]);
+        let look_at = self.look_at.unwrap_or(vector![0.0, 0.0, -1.0]);
+        let vup = self.vup.unwrap_or(vector![0.0, 1.0, 0.0]);
+        let vfov = self.vfov.unwrap_or(90.0);
+        let aspect_ratio = self.aspect_ratio.unwrap_or(16.0 / 9.0);
+        let focal_length = self.focal_length.unwrap_or(1.0);
+        let aperture = self.aperture.unwrap_or(2.0);
+        let focus_dist = self.focus_dist.unwrap_or(1.0);
 
         let w = (origin - look_at).normalize();
         let u = vup.cross(&w).normalize();
@@ -1,7 +1,7 @@
 use super::*;
 
 #[derive(Builder, Debug, Clone)]
-#[builder(build_fn(skip))]
+#[builder(build_fn(skip), derive(Debug))]
 pub struct Canvas {
     pub width: u32,
     pub height: u32,
@@ -13,13 +13,13 @@ pub struct Canvas {
 }
 
 impl Canvas {
-    pub fn save(&self, path: &str) -> Result<(), ImageError> {
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), ImageError> {
         let tmapped_raw = self
             .buffer
             .as_standard_layout()
             .mapv(|x| (x * 255.0) as u8)
             .into_raw_vec();
-        let file = std::fs::File::create(path).unwrap();
+        let file = std::fs::File::create(path)?;
         let encoder = PngEncoder::new(file);
         encoder.encode(
             tmapped_raw.as_bytes(),
@@ -33,14 +33,8 @@ impl Canvas {
 
 impl CanvasBuilder {
     pub fn build(&self) -> Result<Canvas, CanvasBuilderError> {
-        let width = match self.width {
-            Some(value) => value,
-            None => 960,
-        };
-        let height = match self.height {
-            Some(value) => value,
-            None => 540,
-        };
+        let width = self.width.unwrap_or(960);
+        let height = self.height.unwrap_or(540);
 
         let aspect_ratio = width as f64 / height as f64;
         let buffer = Array3::zeros((height as usize, width as usize, 3));
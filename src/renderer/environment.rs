@@ -0,0 +1,135 @@
+use super::*;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// What a ray sees when it escapes the scene.
+#[derive(Debug, Clone)]
+pub enum Environment {
+    Black,
+    Constant(Vector3<f64>),
+    Gradient {
+        horizon: Vector3<f64>,
+        zenith: Vector3<f64>,
+    },
+    Image {
+        map: Arc<EnvironmentMap>,
+        strength: f64,
+    },
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::Gradient {
+            horizon: vector![1.0, 1.0, 1.0],
+            zenith: vector![0.5, 0.7, 1.0],
+        }
+    }
+}
+
+impl Environment {
+    pub fn color(&self, direction: &Vector3<f64>) -> Vector3<f64> {
+        match self {
+            Environment::Black => Vector3::zeros(),
+            Environment::Constant(color) => *color,
+            Environment::Gradient { horizon, zenith } => {
+                let t = 0.5 * (direction.normalize()[1] + 1.0);
+                (1.0 - t) * horizon + t * zenith
+            }
+            Environment::Image { map, strength } => *strength * map.lookup(direction),
+        }
+    }
+}
+
+/// An equirectangular (latitude-longitude) radiance map, +Y is up.
+#[derive(Debug)]
+pub struct EnvironmentMap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vector3<f64>>,
+}
+
+impl EnvironmentMap {
+    /// Load a Radiance `.hdr` file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, ImageError> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let decoder = image::codecs::hdr::HdrDecoder::new(file)?;
+        let metadata = decoder.metadata();
+        if metadata.width == 0 || metadata.height == 0 {
+            return Err(empty_image_error());
+        }
+        let pixels = decoder
+            .read_image_hdr()?
+            .iter()
+            .map(|pixel| pixel.0.map(|x| x as f64).into())
+            .collect();
+        Ok(EnvironmentMap {
+            width: metadata.width as usize,
+            height: metadata.height as usize,
+            pixels,
+        })
+    }
+
+    pub fn lookup(&self, direction: &Vector3<f64>) -> Vector3<f64> {
+        let direction = direction.normalize();
+        let u = 0.5 + direction[0].atan2(-direction[2]) / (2.0 * PI);
+        let v = direction[1].clamp(-1.0, 1.0).acos() / PI;
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_from_horizon_to_zenith() {
+        let environment = Environment::default();
+        assert_eq!(
+            environment.color(&vector![0.0, 2.0, 0.0]),
+            vector![0.5, 0.7, 1.0]
+        );
+        assert_eq!(
+            environment.color(&vector![0.0, -1.0, 0.0]),
+            vector![1.0, 1.0, 1.0]
+        );
+        let sideways = environment.color(&vector![1.0, 0.0, 1.0]);
+        assert!((sideways - vector![0.75, 0.85, 1.0]).norm() < 1e-12);
+    }
+
+    #[test]
+    fn image_lookup_is_equirectangular() {
+        // Pixel i has the value i, 4 columns of longitude and 2 rows of latitude
+        let map = EnvironmentMap {
+            width: 4,
+            height: 2,
+            pixels: (0..8).map(|i| Vector3::repeat(i as f64)).collect(),
+        };
+        let environment = Environment::Image {
+            map: Arc::new(map),
+            strength: 2.0,
+        };
+        let pixel = |direction: Vector3<f64>| environment.color(&direction)[0] / 2.0;
+        // -Z is the center of the image, +X a quarter to the right
+        assert_eq!(pixel(vector![0.0, 0.5, -1.0]), 2.0);
+        assert_eq!(pixel(vector![1.0, 0.5, 0.0]), 3.0);
+        assert_eq!(pixel(vector![-1.0, 0.5, 0.0]), 1.0);
+        assert_eq!(pixel(vector![0.0, -0.5, -1.0]), 6.0);
+        // Any longitude will do at the poles, as long as it's inside the image
+        assert!(pixel(vector![0.0, 1.0, 0.0]) < 4.0);
+        assert!(pixel(vector![0.0, -1.0, 0.0]) >= 4.0);
+    }
+
+    #[test]
+    fn empty_maps_are_rejected() {
+        let path = std::env::temp_dir().join("rustyray-empty.hdr");
+        std::fs::write(&path, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 0 +X 0\n").unwrap();
+        let result = EnvironmentMap::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The parameter is malformed: the image has no pixels"
+        );
+    }
+}
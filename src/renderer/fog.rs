@@ -0,0 +1,109 @@
+use super::*;
+
+/// Exponential height fog filling the whole scene, camera included. Instead of scattering
+/// rays it fades what each path segment sees towards `color` with the segment's
+/// transmittance, which gives distant objects and the horizon their aerial perspective.
+#[derive(Builder, Debug, Clone)]
+pub struct Fog {
+    /// Extinction per unit length at `height`
+    #[builder(default = "0.05")]
+    pub density: f64,
+    /// Where the density is `density`, it thins out above and thickens below
+    #[builder(default = "0.0")]
+    pub height: f64,
+    /// How fast the density falls off per unit of height, 0 for uniform fog
+    #[builder(default = "0.5")]
+    pub falloff: f64,
+    /// Radiance scattered into a segment by the fog itself
+    #[builder(default = "vector![0.7, 0.75, 0.8]")]
+    pub color: Vector3<f64>,
+}
+
+impl Fog {
+    /// The fraction of light that passes through the fog along `distance` of `ray`, which
+    /// may be infinite for a ray that escapes the scene.
+    pub fn transmittance(&self, ray: &Ray, distance: f64) -> f64 {
+        if distance <= 0.0 || self.density <= 0.0 {
+            return 1.0;
+        }
+        let direction = ray.direction.normalize();
+        // Density along the ray is exponential in the distance, integrate it in closed form
+        let start = self.density * (-self.falloff * (ray.origin[1] - self.height)).exp();
+        if start <= 0.0 {
+            return 1.0;
+        }
+        let rate = self.falloff * direction[1];
+        let length = if rate.abs() < 1e-9 {
+            distance
+        } else {
+            (1.0 - (-rate * distance).exp()) / rate
+        };
+        (-start * length).exp()
+    }
+
+    /// What is seen through `distance` of fog along `ray` when `radiance` arrives at its end.
+    pub fn apply<R: Radiance>(&self, radiance: R, ray: &Ray, distance: f64) -> R {
+        let transmittance = self.transmittance(ray, distance);
+        radiance * transmittance + R::from_rgb(&(self.color * (1.0 - transmittance)), ray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: Vector3<f64>, direction: Vector3<f64>) -> Ray {
+        Ray {
+            origin,
+            direction,
+            wavelengths: None,
+        }
+    }
+
+    #[test]
+    fn uniform_fog_is_exponential() {
+        let fog = FogBuilder::default()
+            .density(0.1)
+            .falloff(0.0)
+            .build()
+            .unwrap();
+        let ray = ray(vector![0.0, 3.0, 0.0], vector![1.0, 1.0, 0.0]);
+        assert!((fog.transmittance(&ray, 5.0) - (-0.5f64).exp()).abs() < 1e-12);
+        assert_eq!(fog.transmittance(&ray, f64::INFINITY), 0.0);
+    }
+
+    #[test]
+    fn height_fog_matches_numeric_integration() {
+        let fog = FogBuilder::default()
+            .density(0.2)
+            .height(1.0)
+            .falloff(0.7)
+            .build()
+            .unwrap();
+        let ray = ray(vector![0.0, -1.0, 0.0], vector![0.6, 0.8, 0.0]);
+        let steps = 100_000;
+        let distance = 4.0;
+        let optical_depth: f64 = (0..steps)
+            .map(|i| {
+                let y = ray.at((i as f64 + 0.5) / steps as f64 * distance)[1];
+                fog.density * (-fog.falloff * (y - fog.height)).exp() * distance / steps as f64
+            })
+            .sum();
+        let transmittance = fog.transmittance(&ray, distance);
+        assert!((transmittance - (-optical_depth).exp()).abs() < 1e-6);
+
+        // Rays going up escape through a finite amount of fog
+        let escaping = fog.transmittance(&ray, f64::INFINITY);
+        assert!(escaping > 0.0 && escaping < transmittance);
+    }
+
+    #[test]
+    fn apply_fades_to_the_fog_color() {
+        let fog = FogBuilder::default().build().unwrap();
+        let ray = ray(vector![0.0, 0.0, 0.0], vector![0.0, 0.0, -1.0]);
+        let radiance = vector![1.0, 0.0, 0.0];
+        assert_eq!(fog.apply(radiance, &ray, 0.0), radiance);
+        let far = fog.apply(radiance, &ray, f64::INFINITY);
+        assert!((far - fog.color).norm() < 1e-12);
+    }
+}
@@ -1,4 +1,6 @@
 use super::*;
+use rayon::ThreadPool;
+use std::sync::Arc;
 
 mod camera;
 pub use camera::*;
@@ -9,20 +11,43 @@ pub use canvas::*;
 mod ray;
 pub use ray::*;
 
+mod environment;
+pub use environment::*;
+
+mod fog;
+pub use fog::*;
+
+mod world;
+pub use world::*;
+
 #[derive(Builder, Debug)]
 #[builder(build_fn(skip))]
 // TODO lifetimes
 pub struct Renderer<'a> {
     samples: u32,
     max_depth: u32,
-    thread_count: u32,
+    /// Set through `thread_count`
+    #[builder(setter(custom))]
+    pool: Arc<ThreadPool>,
+    #[builder(setter(strip_option))]
+    seed: Option<u64>,
 
     gamma: f64,
+    /// Trace hero wavelengths instead of RGB
+    spectral: bool,
 
     pub camera: Camera,
     canvas: Canvas,
 
-    scene_objects: &'a [Object<'a>],
+    /// Set through `scene_objects`, like `lights`
+    #[builder(setter(custom))]
+    bvh: BvhNode<&'a Object<'a>>,
+    #[builder(setter(custom))]
+    lights: Vec<&'a Object<'a>>,
+    environment: Environment,
+    /// Haze the camera and the whole scene sit in
+    #[builder(setter(strip_option))]
+    fog: Option<Fog>,
 
     #[builder(setter(skip))]
     progress_bar: ProgressBar,
@@ -30,53 +55,87 @@ pub struct Renderer<'a> {
 
 impl Renderer<'_> {
     pub fn render(&mut self) {
-        Zip::indexed(self.canvas.buffer.lanes_mut(Axis(2))).par_for_each(|(j, i), mut pixel| {
-            let mut accum_color = vector![0.0, 0.0, 0.0];
-            let mut rng = thread_rng();
-            for _ in 0..self.samples {
-                let u = (i as f64 + rng.gen::<f64>()) / (self.canvas.width - 1) as f64;
-                let v = (j as f64 + rng.gen::<f64>()) / (self.canvas.height - 1) as f64;
-                // TODO move to camera
-                let ray = self.camera.get_ray(u, v);
-                accum_color += &ray.get_color(&self.scene_objects, self.max_depth);
-            }
-            // TODO allow manual gamma correction
-            let arr = Array1::from_iter((accum_color / self.samples as f64).iter().cloned());
-            pixel.assign(&arr);
-            self.progress_bar.inc(1);
+        let width = self.canvas.width as usize;
+        let world = World {
+            objects: &self.bvh,
+            lights: &self.lights,
+            environment: &self.environment,
+            fog: self.fog.as_ref(),
+        };
+        self.pool.install(|| {
+            Zip::indexed(self.canvas.buffer.lanes_mut(Axis(2))).par_for_each(
+                |(j, i), mut pixel| {
+                    // Seed per pixel so the result doesn't depend on thread scheduling
+                    if let Some(seed) = self.seed {
+                        seed_render_rng(
+                            seed ^ ((j * width + i) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15),
+                        );
+                    }
+                    let mut accum_color = vector![0.0, 0.0, 0.0];
+                    let mut rng = render_rng();
+                    for _ in 0..self.samples {
+                        let u = (i as f64 + rng.gen::<f64>()) / (self.canvas.width - 1) as f64;
+                        let v = (j as f64 + rng.gen::<f64>()) / (self.canvas.height - 1) as f64;
+                        // TODO move to camera
+                        let mut ray = self.camera.get_ray(u, v);
+                        if self.spectral {
+                            ray.wavelengths = Some(Wavelengths::sample());
+                        }
+                        accum_color += &ray.get_color(&world, self.max_depth);
+                    }
+                    // TODO allow manual gamma correction
+                    let arr =
+                        Array1::from_iter((accum_color / self.samples as f64).iter().cloned());
+                    pixel.assign(&arr);
+                    self.progress_bar.inc(1);
+                },
+            );
         });
+        self.progress_bar.finish_and_clear();
     }
-    pub fn save_render(&mut self, path: &str) {
-        // TODO error
+    pub fn save_render(&mut self, path: impl AsRef<std::path::Path>) -> Result<(), ImageError> {
         self.canvas
             .buffer
             .mapv_inplace(|x| x.powf(1.0 / self.gamma));
-        self.canvas.save(path).expect("Failed to save the render.");
+        self.canvas.save(path)
     }
 }
 
 impl<'a> RendererBuilder<'a> {
+    pub fn thread_count(&mut self, thread_count: u32) -> &mut Self {
+        self.pool = Some(thread_pool(thread_count));
+        self
+    }
+
+    /// The objects to render, put into a BVH and searched for lights right away.
+    pub fn scene_objects(&mut self, scene_objects: &'a [Object<'a>]) -> &mut Self {
+        self.bvh = Some(BvhNode::new(scene_objects.iter().collect()));
+        self.lights = Some(
+            scene_objects
+                .iter()
+                .filter(|object| {
+                    matches!(object.material(), Material::Emissive(_))
+                        && !matches!(object, Object::Medium(_) | Object::Volume(_))
+                })
+                .collect(),
+        );
+        self
+    }
+
     pub fn build(&self) -> Result<Renderer<'a>, RendererBuilderError> {
-        let samples = match self.samples {
-            Some(value) => value,
-            None => 500,
-        };
-        let max_depth = match self.max_depth {
-            Some(value) => value,
-            None => 50,
-        };
-        let thread_count = match self.thread_count {
-            Some(value) => value,
-            None => 8,
+        let samples = self.samples.unwrap_or(500);
+        let max_depth = self.max_depth.unwrap_or(50);
+        let pool = match self.pool {
+            Some(ref value) => value.clone(),
+            None => thread_pool(8),
         };
+        let seed = self.seed.unwrap_or(None);
         let canvas = match self.canvas {
             Some(ref value) => (*value).clone(),
             None => CanvasBuilder::default().build().unwrap(),
         };
-        let gamma = match self.gamma {
-            Some(value) => value,
-            None => 2.0,
-        };
+        let gamma = self.gamma.unwrap_or(2.0);
+        let spectral = self.spectral.unwrap_or(false);
         let camera = match self.camera {
             Some(ref value) => {
                 let mut camera = (*value).clone();
@@ -92,28 +151,41 @@ impl<'a> RendererBuilder<'a> {
             }
             None => CameraBuilder::default().build().unwrap(),
         };
-        let scene_objects = match self.scene_objects {
-            Some(value) => value,
-            None => return Result::Err(Into::into(UninitializedFieldError::from("scene_objects"))),
+        let (bvh, lights) = match (&self.bvh, &self.lights) {
+            (Some(bvh), Some(lights)) => (bvh.clone(), lights.clone()),
+            _ => return Result::Err(Into::into(UninitializedFieldError::from("scene_objects"))),
         };
 
-        let pixel_count = (canvas.width * canvas.height) as u64;
+        let environment = match self.environment {
+            Some(ref value) => value.clone(),
+            None => Environment::default(),
+        };
+        let fog = self.fog.clone().unwrap_or(None);
+        let pixel_count = canvas.width as u64 * canvas.height as u64;
         let progress_bar = ProgressBar::new(pixel_count);
 
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(thread_count as usize)
-            .build_global()
-            .unwrap();
-
         Ok(Renderer {
             samples,
             max_depth,
-            thread_count,
+            pool,
+            seed,
             camera,
             canvas,
-            scene_objects,
+            bvh,
+            lights,
+            environment,
+            fog,
             progress_bar,
             gamma,
+            spectral,
         })
     }
 }
+
+fn thread_pool(thread_count: u32) -> Arc<ThreadPool> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(thread_count as usize)
+        .build()
+        .expect("Failed to build the thread pool.");
+    Arc::new(pool)
+}
@@ -4,47 +4,154 @@ use super::*;
 pub struct Ray {
     pub origin: Vector3<f64>,
     pub direction: Vector3<f64>,
+    /// Set for every ray in spectral mode, or once a dispersive material has split an RGB
+    /// path off white light
+    pub wavelengths: Option<Wavelengths>,
 }
 
 impl Ray {
-    pub fn get_color<'b, 'a: 'b, T: Hit<'b, 'a>>(
+    pub fn get_color(&self, world: &World, depth: u32) -> Vector3<f64> {
+        match self.wavelengths {
+            Some(wavelengths) => wavelengths.to_rgb(&self.trace(world, depth, None, false)),
+            None => self.trace(world, depth, None, false),
+        }
+    }
+
+    /// `bsdf_pdf` is the density with which this ray was sampled at the previous bounce,
+    /// or `None` if it came from the camera or a specular bounce. `inside` is whether the
+    /// ray runs through a transmissive object, where there is no fog.
+    fn trace<R: Radiance>(
         &self,
-        scene_objs: &'a T,
+        world: &World,
         depth: u32,
-    ) -> Vector3<f64> {
+        bsdf_pdf: Option<f64>,
+        inside: bool,
+    ) -> R {
         if depth == 0 {
-            return vector![0.0, 0.0, 0.0];
+            return R::zero();
         }
         let mut hit_rec = HitRecord::new(&Material::None);
         // TODO Range?
-        if scene_objs.hit(self, 0.001..f64::INFINITY, &mut hit_rec) {
+        if world.hit(self, 0.001..f64::INFINITY, &mut hit_rec) {
             #[cfg(debug_assertions)]
             if NORMAL {
-                return 0.5
+                let color = 0.5
                     * vector![
                         hit_rec.normal[0] + 1.0,
                         hit_rec.normal[1] + 1.0,
                         hit_rec.normal[2] + 1.0
                     ];
+                return R::from_rgb(&color, self);
             }
+            let radiance: R = self.shade(world, &mut hit_rec, depth, bsdf_pdf, inside);
+            let distance = hit_rec.t * self.direction.norm();
+            let transmittance = hit_rec.material.transmittance(&hit_rec, distance);
+            let radiance = radiance.component_mul(&R::from_rgb(&transmittance, self));
+            return match world.fog {
+                Some(fog) if !inside => fog.apply(radiance, self, distance),
+                _ => radiance,
+            };
+        }
 
-            let (scattered_ray, attenuation) = hit_rec.material.scatter(self, &hit_rec);
-            match scattered_ray {
-                Some(mut ray) => {
-                    if ray.direction.is_near_zero() {
-                        ray.direction = hit_rec.normal;
-                    }
-                    return ray
-                        .get_color(scene_objs, depth - 1)
-                        .component_mul(attenuation);
+        let radiance = R::from_rgb(&world.environment.color(&self.direction), self);
+        match world.fog {
+            Some(fog) => fog.apply(radiance, self, f64::INFINITY),
+            None => radiance,
+        }
+    }
+
+    /// Light leaving the surface or medium in `hit_rec` back along this ray.
+    fn shade<R: Radiance>(
+        &self,
+        world: &World,
+        hit_rec: &mut HitRecord,
+        depth: u32,
+        bsdf_pdf: Option<f64>,
+        inside: bool,
+    ) -> R {
+        let material = hit_rec.material;
+        if let Some(normal_map) = material.normal_map() {
+            normal_map.apply(hit_rec);
+        }
+
+        let mut emitted = material.emitted(hit_rec);
+        if let Some(bsdf_pdf) = bsdf_pdf {
+            if !emitted.is_near_zero() {
+                emitted *= power_heuristic(bsdf_pdf, world.light_pdf(self, hit_rec));
+            }
+        }
+
+        // Next-event estimation
+        let mut direct = R::zero();
+        if !material.is_specular() {
+            if let Some(sample) = world.sample_light(hit_rec) {
+                let bsdf = material.eval(self, hit_rec, &sample.direction);
+                let transmittance = if bsdf.is_near_zero() {
+                    0.0
+                } else {
+                    world.transmittance(&hit_rec.point, &sample.point)
+                };
+                if transmittance > 0.0 {
+                    let weight =
+                        power_heuristic(sample.pdf, material.pdf(self, hit_rec, &sample.direction));
+                    direct = R::from_rgb(&sample.radiance, self)
+                        .component_mul(&R::from_rgb(&bsdf, self))
+                        * (weight * transmittance / sample.pdf);
                 }
-                None => return vector![0.0, 0.0, 0.0],
             }
         }
 
-        let unit_dir = 0.5 * (self.direction.normalize().add_scalar(1.0));
-        // Colors
-        (1.0 - unit_dir[1]) * vector![1.0, 1.0, 1.0] + unit_dir[1] * vector![0.5, 0.7, 1.0]
+        let emitted = R::from_rgb(&emitted, self);
+        match material.sample(self, hit_rec) {
+            Some(sample) => {
+                let ray = Ray {
+                    origin: hit_rec.point,
+                    direction: sample.direction,
+                    wavelengths: sample.wavelengths,
+                };
+                let pdf = (!sample.is_specular).then_some(sample.pdf);
+                // The normal faces the incoming ray, so going below it crosses the surface
+                let inside =
+                    inside != (material.transmits() && sample.direction.dot(&hit_rec.normal) < 0.0);
+                let incoming = match material {
+                    // Light going inside wanders around until it leaves somewhere else
+                    Material::Subsurface(subsurface)
+                        if hit_rec.front_face && sample.direction.dot(&hit_rec.normal) < 0.0 =>
+                    {
+                        ray.trace_subsurface::<R>(world, depth - 1, subsurface)
+                    }
+                    _ => match (self.wavelengths, ray.wavelengths) {
+                        // An RGB path split onto a single wavelength by a dispersive material
+                        (None, Some(wavelengths)) => R::from_rgb(
+                            &wavelengths.to_rgb(&ray.trace(world, depth - 1, pdf, inside)),
+                            self,
+                        ),
+                        _ => ray.trace::<R>(world, depth - 1, pdf, inside),
+                    },
+                };
+                emitted + direct + incoming.component_mul(&R::from_rgb(&sample.weight, &ray))
+            }
+            None => emitted + direct,
+        }
+    }
+
+    /// Continues a path that entered `subsurface` along this ray from where its random walk
+    /// leaves the object again.
+    fn trace_subsurface<R: Radiance>(
+        &self,
+        world: &World,
+        depth: u32,
+        subsurface: &Subsurface,
+    ) -> R {
+        if depth == 0 {
+            return R::zero();
+        }
+        match subsurface.walk(self, world) {
+            Some((ray, mut exit, throughput)) => ray
+                .shade::<R>(world, &mut exit, depth, None, false)
+                .component_mul(&R::from_rgb(&throughput, &ray)),
+            None => R::zero(),
+        }
     }
 
     pub fn at(&self, t: f64) -> Vector3<f64> {
@@ -52,13 +159,29 @@ impl Ray {
     }
 }
 
+/// Multiple importance sampling weight for a sample from the strategy with `pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    if pdf <= 0.0 {
+        return 0.0;
+    }
+    pdf.powi(2) / (pdf.powi(2) + other_pdf.powi(2))
+}
+
 #[derive(Clone, Debug)]
 pub struct HitRecord<'a> {
     pub point: Vector3<f64>,
     pub normal: Vector3<f64>,
     pub t: f64,
+    /// Texture coordinates
+    pub uv: Vector2<f64>,
+    /// ∂p/∂u, not normalized
+    pub tangent: Vector3<f64>,
+    /// ∂p/∂v, not normalized
+    pub bitangent: Vector3<f64>,
     pub front_face: bool,
     pub material: &'a Material,
+    /// Surface area of the object that was hit, to evaluate light sampling pdfs
+    pub area: f64,
 }
 
 impl<'a> HitRecord<'a> {
@@ -67,10 +190,34 @@ impl<'a> HitRecord<'a> {
             point: Vector3::zeros(),
             normal: Vector3::zeros(),
             t: 0.0,
+            uv: Vector2::zeros(),
+            tangent: Vector3::zeros(),
+            bitangent: Vector3::zeros(),
             front_face: true,
             material,
+            area: 0.0,
         }
     }
+    /// An orthonormal tangent and bitangent around the normal, aligned with ∂p/∂u and ∂p/∂v.
+    pub fn tangent_frame(&self) -> (Vector3<f64>, Vector3<f64>) {
+        let normal = &self.normal;
+        let projected = self.tangent - self.tangent.dot(normal) * normal;
+        let tangent = projected.try_normalize(1e-12).unwrap_or_else(|| {
+            // Degenerate parametrization, any perpendicular will do
+            let axis = if normal[0].abs() > 0.9 {
+                vector![0.0, 1.0, 0.0]
+            } else {
+                vector![1.0, 0.0, 0.0]
+            };
+            normal.cross(&axis).normalize()
+        });
+        let mut bitangent = normal.cross(&tangent);
+        if bitangent.dot(&self.bitangent) < 0.0 {
+            bitangent = -bitangent;
+        }
+        (tangent, bitangent)
+    }
+
     pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: Vector3<f64>) {
         self.front_face = ray.direction.dot(&outward_normal) < 0.0;
         if self.front_face {
@@ -80,3 +227,53 @@ impl<'a> HitRecord<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        for (pdf, other_pdf) in [(1.0, 1.0), (0.3, 2.5), (4.0, 0.0)] {
+            let sum = power_heuristic(pdf, other_pdf) + power_heuristic(other_pdf, pdf);
+            assert!((sum - 1.0).abs() < 1e-12);
+        }
+        assert_eq!(power_heuristic(2.0, 2.0), 0.5);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+        assert_eq!(power_heuristic(0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn fog_in_front_of_back_faces() {
+        let black = Material::Diffuse(
+            DiffuseBuilder::default()
+                .albedo(vector![0.0, 0.0, 0.0])
+                .build()
+                .unwrap(),
+        );
+        // Facing +z, seen from below
+        let triangle = Object::Triangle(Triangle::new(
+            [
+                vector![-1.0, -1.0, 0.0],
+                vector![1.0, -1.0, 0.0],
+                vector![0.0, 1.0, 0.0],
+            ],
+            &black,
+        ));
+        let fog = FogBuilder::default().falloff(0.0).build().unwrap();
+        let world = World {
+            objects: &BvhNode::new(vec![&triangle]),
+            lights: &[],
+            environment: &Environment::Black,
+            fog: Some(&fog),
+        };
+        let ray = Ray {
+            origin: vector![0.0, 0.0, -2.0],
+            direction: vector![0.0, 0.0, 1.0],
+            wavelengths: None,
+        };
+        let color: Vector3<f64> = ray.trace(&world, 1, None, false);
+        let expected = fog.color * (1.0 - fog.transmittance(&ray, 2.0));
+        assert!((color - expected).norm() < 1e-12);
+    }
+}
@@ -0,0 +1,152 @@
+use super::*;
+
+/// Everything a ray can interact with while it is traced, borrowed from the `Renderer`.
+#[derive(Debug, Clone, Copy)]
+pub struct World<'w, 'a> {
+    pub objects: &'w BvhNode<&'a Object<'a>>,
+    pub lights: &'w [&'a Object<'a>],
+    pub environment: &'w Environment,
+    pub fog: Option<&'w Fog>,
+}
+
+/// A direction towards a point on a light.
+#[derive(Debug, Clone)]
+pub struct LightSample {
+    pub point: Vector3<f64>,
+    pub direction: Vector3<f64>,
+    pub radiance: Vector3<f64>,
+    /// With respect to solid angle, including the choice of light
+    pub pdf: f64,
+}
+
+impl World<'_, '_> {
+    /// Sample a point on a randomly chosen light as seen from `hit_rec`, ignoring occlusion.
+    pub fn sample_light(&self, hit_rec: &HitRecord) -> Option<LightSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let light = self.lights[render_rng().gen_range(0..self.lights.len())];
+        let sample = light.sample_area()?;
+
+        let to_light = sample.point - hit_rec.point;
+        let dist_squared = to_light.norm_squared();
+        let direction = to_light / dist_squared.sqrt();
+        // Lights emit on both sides
+        let cos_light = sample.normal.dot(&direction).abs();
+        if cos_light <= 0.0 {
+            return None;
+        }
+
+        let mut light_rec = HitRecord::new(light.material());
+        light_rec.point = sample.point;
+        light_rec.set_face_normal(
+            &Ray {
+                origin: hit_rec.point,
+                direction,
+                wavelengths: None,
+            },
+            sample.normal,
+        );
+        Some(LightSample {
+            point: sample.point,
+            direction,
+            radiance: light.material().emitted(&light_rec),
+            pdf: sample.pdf / self.lights.len() as f64 * dist_squared / cos_light,
+        })
+    }
+
+    /// The solid angle density with which `sample_light` would pick the point in `hit_rec`
+    /// on an emitter hit by `ray`.
+    pub fn light_pdf(&self, ray: &Ray, hit_rec: &HitRecord) -> f64 {
+        if self.lights.is_empty() || hit_rec.area <= 0.0 {
+            return 0.0;
+        }
+        let dist_squared = (hit_rec.t * ray.direction.norm()).powi(2);
+        let cos_light = hit_rec.normal.dot(&ray.direction.normalize()).abs();
+        dist_squared / (cos_light * hit_rec.area * self.lights.len() as f64)
+    }
+
+    /// The fraction of light that makes it from `from` to `to`, 0 if they can't see each other.
+    pub fn transmittance(&self, from: &Vector3<f64>, to: &Vector3<f64>) -> f64 {
+        let shadow_ray = Ray {
+            origin: *from,
+            direction: to - from,
+            wavelengths: None,
+        };
+        let fog = match self.fog {
+            Some(fog) => fog.transmittance(&shadow_ray, shadow_ray.direction.norm()),
+            None => 1.0,
+        };
+        fog * self.objects.transmittance(&shadow_ray, 0.001..1.0 - 1e-4)
+    }
+}
+
+impl<'b, 'a: 'b> Hit<'b, 'a> for World<'_, 'a> {
+    fn hit(&self, ray: &Ray, t_range: std::ops::Range<f64>, hit_rec: &mut HitRecord<'b>) -> bool {
+        self.objects.hit(ray, t_range, hit_rec)
+    }
+
+    fn hit_any(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> bool {
+        self.objects.hit_any(ray, t_range)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_range: std::ops::Range<f64>) -> f64 {
+        self.objects.transmittance(ray, t_range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_pdf_matches_sample_light() {
+        let light = Material::Emissive(EmissiveBuilder::default().build().unwrap());
+        let triangles = [
+            Object::Triangle(Triangle::new(
+                [
+                    vector![-1.0, 2.0, -1.0],
+                    vector![1.0, 2.0, -1.0],
+                    vector![0.0, 2.0, 1.0],
+                ],
+                &light,
+            )),
+            Object::Triangle(Triangle::new(
+                [
+                    vector![3.0, 0.0, 0.0],
+                    vector![3.0, 1.0, 0.0],
+                    vector![3.0, 0.0, 2.0],
+                ],
+                &light,
+            )),
+        ];
+        let lights: Vec<_> = triangles.iter().collect();
+        let world = World {
+            objects: &BvhNode::new(lights.clone()),
+            lights: &lights,
+            environment: &Environment::Black,
+            fog: None,
+        };
+        let mut hit_rec = HitRecord::new(&Material::None);
+        hit_rec.normal = vector![0.0, 1.0, 0.0];
+        seed_render_rng(7);
+        for _ in 0..100 {
+            let sample = world.sample_light(&hit_rec).unwrap();
+            let ray = Ray {
+                origin: hit_rec.point,
+                direction: sample.direction,
+                wavelengths: None,
+            };
+            let mut light_rec = HitRecord::new(&Material::None);
+            assert!(world.hit(&ray, 0.001..f64::INFINITY, &mut light_rec));
+            assert!((light_rec.point - sample.point).norm() < 1e-9);
+            let pdf = world.light_pdf(&ray, &light_rec);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-9 * sample.pdf,
+                "{} {}",
+                pdf,
+                sample.pdf
+            );
+        }
+    }
+}
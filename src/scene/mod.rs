@@ -0,0 +1,1625 @@
+use super::*;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::Spanned;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, std::io::Error),
+    Parse {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    Obj(ObjError),
+    Image(PathBuf, ImageError),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            SceneError::Parse {
+                path,
+                line: Some(line),
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            SceneError::Parse {
+                path,
+                line: None,
+                message,
+            } => write!(f, "{}: {}", path.display(), message),
+            SceneError::Obj(err) => err.fmt(f),
+            SceneError::Image(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<ObjError> for SceneError {
+    fn from(err: ObjError) -> Self {
+        SceneError::Obj(err)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    camera: CameraDescription,
+    #[serde(default)]
+    canvas: CanvasDescription,
+    #[serde(default)]
+    renderer: RendererDescription,
+    #[serde(default)]
+    textures: HashMap<String, TextureDescription>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDescription>,
+    #[serde(default)]
+    objects: Vec<ObjectDescription>,
+    environment: Option<EnvironmentDescription>,
+    fog: Option<FogDescription>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    origin: Option<[f64; 3]>,
+    look_at: Option<[f64; 3]>,
+    vup: Option<[f64; 3]>,
+    vfov: Option<f64>,
+    focal_length: Option<f64>,
+    aperture: Option<f64>,
+    focus_dist: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CanvasDescription {
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RendererDescription {
+    samples: Option<u32>,
+    max_depth: Option<u32>,
+    gamma: Option<f64>,
+    thread_count: Option<u32>,
+    spectral: Option<bool>,
+}
+
+/// Declares the table of something in a scene file that has a `type`, like a material, with
+/// `properties` naming the optional fields that were given so that `Validator::allow` can
+/// check them against the type. Fields every type takes go before the `;` and aren't named.
+/// The tables are flat instead of tagged enums so that spans survive deserialization.
+macro_rules! description {
+    (
+        struct $name:ident {
+            $($shared:ident: $shared_ty:ty,)*
+            ;
+            $($property:ident: Option<$property_ty:ty>,)*
+        }
+    ) => {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct $name {
+            #[serde(rename = "type")]
+            kind: Spanned<String>,
+            $($shared: $shared_ty,)*
+            $($property: Option<$property_ty>,)*
+        }
+
+        impl $name {
+            fn properties(&self) -> Vec<&'static str> {
+                let given = [$((stringify!($property), self.$property.is_some())),*];
+                given
+                    .iter()
+                    .filter(|(_, given)| *given)
+                    .map(|(name, _)| *name)
+                    .collect()
+            }
+        }
+    };
+}
+
+description! {
+    struct EnvironmentDescription {
+        ;
+        color: Option<Spanned<[f64; 3]>>,
+        horizon: Option<Spanned<[f64; 3]>>,
+        zenith: Option<Spanned<[f64; 3]>>,
+        path: Option<String>,
+        strength: Option<Spanned<f64>>,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FogDescription {
+    density: Option<Spanned<f64>>,
+    height: Option<Spanned<f64>>,
+    falloff: Option<Spanned<f64>>,
+    color: Option<Spanned<[f64; 3]>>,
+}
+
+description! {
+    struct TextureDescription {
+        ;
+        color: Option<Spanned<[f64; 3]>>,
+        even: Option<Spanned<[f64; 3]>>,
+        odd: Option<Spanned<[f64; 3]>>,
+        scale: Option<Spanned<f64>>,
+        width: Option<u32>,
+        height: Option<u32>,
+        path: Option<String>,
+        wrap: Option<Spanned<String>>,
+        filter: Option<Spanned<String>>,
+        srgb: Option<bool>,
+        octaves: Option<u32>,
+        seed: Option<u64>,
+        low: Option<Spanned<[f64; 3]>>,
+        high: Option<Spanned<[f64; 3]>>,
+    }
+}
+
+description! {
+    struct MaterialDescription {
+        ;
+        albedo: Option<Spanned<[f64; 3]>>,
+        albedo_texture: Option<Spanned<String>>,
+        normal_map: Option<Spanned<String>>,
+        bump_map: Option<Spanned<String>>,
+        bump_strength: Option<Spanned<f64>>,
+        roughness: Option<Spanned<f64>>,
+        anisotropy: Option<Spanned<f64>>,
+        preset: Option<Spanned<String>>,
+        eta: Option<Spanned<[f64; 3]>>,
+        k: Option<Spanned<[f64; 3]>>,
+        absorption: Option<Spanned<[f64; 3]>>,
+        transmission_color: Option<Spanned<[f64; 3]>>,
+        transmission_distance: Option<Spanned<f64>>,
+        dispersion: Option<Spanned<String>>,
+        cauchy: Option<Spanned<[f64; 2]>>,
+        sellmeier: Option<Spanned<[[f64; 3]; 2]>>,
+        film_thickness: Option<Spanned<f64>>,
+        film_ir: Option<Spanned<f64>>,
+        asymmetry: Option<Spanned<f64>>,
+        metallic: Option<Spanned<f64>>,
+        specular: Option<Spanned<f64>>,
+        transmission: Option<Spanned<f64>>,
+        clearcoat: Option<Spanned<f64>>,
+        clearcoat_roughness: Option<Spanned<f64>>,
+        sheen: Option<Spanned<f64>>,
+        sheen_tint: Option<Spanned<f64>>,
+        scattering: Option<Spanned<[f64; 3]>>,
+        mean_free_path: Option<Spanned<[f64; 3]>>,
+        ir: Option<Spanned<f64>>,
+        color: Option<Spanned<[f64; 3]>>,
+        strength: Option<Spanned<f64>>,
+    }
+}
+
+description! {
+    struct ObjectDescription {
+        material: Option<Spanned<String>>,
+        ;
+        center: Option<Spanned<[f64; 3]>>,
+        radius: Option<Spanned<f64>>,
+        vertices: Option<Spanned<[[f64; 3]; 3]>>,
+        path: Option<String>,
+        density: Option<Spanned<f64>>,
+        boundary: Option<Box<ObjectDescription>>,
+        bounds: Option<Spanned<[[f64; 3]; 2]>>,
+        grid: Option<String>,
+        resolution: Option<Spanned<[usize; 3]>>,
+        texture: Option<Spanned<String>>,
+    }
+}
+
+#[derive(Debug)]
+enum SceneObject {
+    Sphere {
+        center: Vector3<f64>,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [Vector3<f64>; 3],
+        material: String,
+    },
+    Obj {
+        scene: ObjScene,
+        material: Option<String>,
+    },
+    Medium {
+        boundary: Boundary,
+        density: f64,
+        material: String,
+    },
+    Volume {
+        bounds: Aabb,
+        field: Arc<DensityField>,
+        density: f64,
+        material: String,
+    },
+}
+
+#[derive(Debug)]
+enum Boundary {
+    Sphere { center: Vector3<f64>, radius: f64 },
+    Obj(ObjScene),
+}
+
+/// An owned scene description, loaded from a TOML file.
+#[derive(Debug)]
+pub struct Scene {
+    pub camera: CameraBuilder,
+    pub canvas: CanvasBuilder,
+    pub samples: Option<u32>,
+    pub max_depth: Option<u32>,
+    pub gamma: Option<f64>,
+    pub thread_count: Option<u32>,
+    pub spectral: Option<bool>,
+    pub environment: Option<Environment>,
+    pub fog: Option<Fog>,
+    pub materials: HashMap<String, Material>,
+    objects: Vec<SceneObject>,
+    default_material: Material,
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).map_err(|err| SceneError::Io(path.to_path_buf(), err))?;
+        Self::parse(&source, path)
+    }
+
+    /// Parse a scene from `source`, `path` is used for error messages and to resolve
+    /// relative model paths.
+    pub fn parse(source: &str, path: &Path) -> Result<Self, SceneError> {
+        let file: SceneFile = toml::from_str(source).map_err(|err| {
+            let message = err.to_string();
+            // The position is reported separately
+            let message = match message.rfind(" at line ") {
+                Some(i) => message[..i].to_string(),
+                None => message,
+            };
+            SceneError::Parse {
+                path: path.to_path_buf(),
+                line: err.line_col().map(|(line, _)| line + 1),
+                message,
+            }
+        })?;
+        let validator = Validator {
+            source,
+            path,
+            cache: RefCell::new(TextureCache::default()),
+        };
+
+        let mut camera = CameraBuilder::default();
+        let description = file.camera;
+        if let Some(origin) = description.origin {
+            camera.origin(origin.into());
+        }
+        if let Some(look_at) = description.look_at {
+            camera.look_at(look_at.into());
+        }
+        if let Some(vup) = description.vup {
+            camera.vup(vup.into());
+        }
+        if let Some(vfov) = description.vfov {
+            camera.vfov(vfov);
+        }
+        if let Some(focal_length) = description.focal_length {
+            camera.focal_length(focal_length);
+        }
+        if let Some(aperture) = description.aperture {
+            camera.aperture(aperture);
+        }
+        if let Some(focus_dist) = description.focus_dist {
+            camera.focus_dist(focus_dist);
+        }
+
+        let mut canvas = CanvasBuilder::default();
+        if let Some(width) = file.canvas.width {
+            canvas.width(width);
+        }
+        if let Some(height) = file.canvas.height {
+            canvas.height(height);
+        }
+
+        let environment = match file.environment {
+            Some(description) => Some(validator.environment(description)?),
+            None => None,
+        };
+
+        let fog = match file.fog {
+            Some(description) => Some(validator.fog(description)?),
+            None => None,
+        };
+
+        let colors: HashSet<&str> = file
+            .materials
+            .values()
+            .filter_map(|description| description.albedo_texture.as_ref())
+            .map(|name| name.get_ref().as_str())
+            .collect();
+        // Normal and bump maps hold data rather than colors
+        let maps: HashSet<&str> = file
+            .materials
+            .values()
+            .flat_map(|description| [&description.normal_map, &description.bump_map])
+            .flatten()
+            .map(|name| name.get_ref().as_str())
+            .collect();
+        let mut textures = HashMap::new();
+        for (name, description) in file.textures {
+            let srgb = match (colors.contains(name.as_str()), maps.contains(name.as_str())) {
+                (true, true) => None,
+                (_, map) => Some(!map),
+            };
+            textures.insert(name, validator.texture(description, srgb)?);
+        }
+
+        let mut materials = HashMap::new();
+        for (name, description) in file.materials {
+            materials.insert(name, validator.material(description, &textures)?);
+        }
+
+        let objects = file
+            .objects
+            .into_iter()
+            .map(|description| validator.object(description, &materials, &textures))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Scene {
+            camera,
+            canvas,
+            samples: file.renderer.samples,
+            max_depth: file.renderer.max_depth,
+            gamma: file.renderer.gamma,
+            thread_count: file.renderer.thread_count,
+            spectral: file.renderer.spectral,
+            environment,
+            fog,
+            materials,
+            objects,
+            default_material: Material::Diffuse(
+                DiffuseBuilder::default()
+                    .albedo(vector![0.8, 0.8, 0.8])
+                    .build()
+                    .unwrap(),
+            ),
+        })
+    }
+
+    pub fn objects(&self) -> Vec<Object<'_>> {
+        let mut objects = vec![];
+        for object in &self.objects {
+            self.push_objects(object, &mut objects);
+        }
+        objects
+    }
+
+    fn push_objects<'s>(&'s self, object: &'s SceneObject, objects: &mut Vec<Object<'s>>) {
+        match object {
+            SceneObject::Sphere {
+                center,
+                radius,
+                material,
+            } => objects.push(Object::Sphere(
+                SphereBuilder::default()
+                    .center(*center)
+                    .radius(*radius)
+                    .material(&self.materials[material])
+                    .build()
+                    .unwrap(),
+            )),
+            SceneObject::Triangle { vertices, material } => objects.push(Object::Triangle(
+                Triangle::new(*vertices, &self.materials[material]),
+            )),
+            SceneObject::Obj { scene, material } => {
+                let default_material = match material {
+                    Some(material) => &self.materials[material],
+                    None => &self.default_material,
+                };
+                objects.extend(scene.objects(default_material));
+            }
+            SceneObject::Medium {
+                boundary,
+                density,
+                material,
+            } => {
+                let material = &self.materials[material];
+                // Every object of an OBJ boundary becomes a medium of its own
+                let boundaries = match boundary {
+                    Boundary::Sphere { center, radius } => vec![Object::Sphere(
+                        SphereBuilder::default()
+                            .center(*center)
+                            .radius(*radius)
+                            .material(material)
+                            .build()
+                            .unwrap(),
+                    )],
+                    Boundary::Obj(scene) => scene.objects(material),
+                };
+                objects.extend(boundaries.into_iter().map(|boundary| {
+                    Object::Medium(ConstantMedium::new(boundary, *density, material))
+                }));
+            }
+            SceneObject::Volume {
+                bounds,
+                field,
+                density,
+                material,
+            } => objects.push(Object::Volume(HeterogeneousMedium::new(
+                *bounds,
+                field.clone(),
+                *density,
+                &self.materials[material],
+            ))),
+        }
+    }
+
+    /// A renderer builder with every setting from the scene file applied.
+    pub fn renderer<'a>(&self, objects: &'a [Object<'a>]) -> RendererBuilder<'a> {
+        let mut builder = RendererBuilder::default();
+        builder
+            .scene_objects(objects)
+            .camera(self.camera.build().unwrap())
+            .canvas(self.canvas.build().unwrap());
+        if let Some(samples) = self.samples {
+            builder.samples(samples);
+        }
+        if let Some(max_depth) = self.max_depth {
+            builder.max_depth(max_depth);
+        }
+        if let Some(gamma) = self.gamma {
+            builder.gamma(gamma);
+        }
+        if let Some(thread_count) = self.thread_count {
+            builder.thread_count(thread_count);
+        }
+        if let Some(spectral) = self.spectral {
+            builder.spectral(spectral);
+        }
+        if let Some(ref environment) = self.environment {
+            builder.environment(environment.clone());
+        }
+        if let Some(ref fog) = self.fog {
+            builder.fog(fog.clone());
+        }
+        builder
+    }
+}
+
+const POSITIVE: &str = "finite and positive";
+const NOT_NEGATIVE: &str = "finite and not negative";
+const UNIT: &str = "between 0 and 1";
+
+/// Property values made of numbers, which are checked one at a time.
+trait Numbers: Copy {
+    fn numbers(&self) -> Vec<f64>;
+}
+
+impl Numbers for f64 {
+    fn numbers(&self) -> Vec<f64> {
+        vec![*self]
+    }
+}
+
+impl<T: Numbers, const N: usize> Numbers for [T; N] {
+    fn numbers(&self) -> Vec<f64> {
+        self.iter().flat_map(Numbers::numbers).collect()
+    }
+}
+
+struct Validator<'s> {
+    source: &'s str,
+    path: &'s Path,
+    /// Shared by image textures and OBJ materials
+    cache: RefCell<TextureCache>,
+}
+
+impl Validator<'_> {
+    fn error<T>(&self, span: (usize, usize), message: impl Into<String>) -> Result<T, SceneError> {
+        let line = self.source[..span.0].matches('\n').count() + 1;
+        Err(SceneError::Parse {
+            path: self.path.to_path_buf(),
+            line: Some(line),
+            message: message.into(),
+        })
+    }
+
+    /// Fail if a property that doesn't apply to `kind` was given.
+    fn allow(
+        &self,
+        kind: &Spanned<String>,
+        given: &[&str],
+        allowed: &[&str],
+    ) -> Result<(), SceneError> {
+        match given.iter().find(|property| !allowed.contains(property)) {
+            Some(property) => self.error(
+                kind.span(),
+                format!("'{}' is not a property of {}", property, kind.get_ref()),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// The value of the property `name` if all its numbers are finite and `valid`, otherwise
+    /// an error at the property saying what it `must` be.
+    fn number<T: Numbers>(
+        &self,
+        name: &str,
+        value: &Spanned<T>,
+        must: &str,
+        valid: impl Fn(f64) -> bool,
+    ) -> Result<T, SceneError> {
+        let numbers = value.get_ref().numbers();
+        if numbers.iter().all(|x| x.is_finite() && valid(*x)) {
+            Ok(*value.get_ref())
+        } else {
+            self.error(value.span(), format!("'{}' must be {}", name, must))
+        }
+    }
+
+    fn finite<T: Numbers>(&self, name: &str, value: &Spanned<T>) -> Result<T, SceneError> {
+        self.number(name, value, "finite", |_| true)
+    }
+
+    fn environment(&self, description: EnvironmentDescription) -> Result<Environment, SceneError> {
+        let kind = &description.kind;
+        let given = description.properties();
+        match kind.get_ref().as_str() {
+            "black" => {
+                self.allow(kind, &given, &[])?;
+                Ok(Environment::Black)
+            }
+            "constant" => {
+                self.allow(kind, &given, &["color"])?;
+                match description.color {
+                    Some(color) => Ok(Environment::Constant(
+                        self.number("color", &color, NOT_NEGATIVE, |x| x >= 0.0)?
+                            .into(),
+                    )),
+                    None => self.error(kind.span(), "Missing 'color' for constant"),
+                }
+            }
+            "gradient" => {
+                self.allow(kind, &given, &["horizon", "zenith"])?;
+                let mut environment = Environment::default();
+                if let Environment::Gradient { horizon, zenith } = &mut environment {
+                    if let Some(value) = description.horizon {
+                        *horizon = self
+                            .number("horizon", &value, NOT_NEGATIVE, |x| x >= 0.0)?
+                            .into();
+                    }
+                    if let Some(value) = description.zenith {
+                        *zenith = self
+                            .number("zenith", &value, NOT_NEGATIVE, |x| x >= 0.0)?
+                            .into();
+                    }
+                }
+                Ok(environment)
+            }
+            "image" => {
+                self.allow(kind, &given, &["path", "strength"])?;
+                let path = match description.path {
+                    Some(path) => self.path.with_file_name(path),
+                    None => return self.error(kind.span(), "Missing 'path' for image"),
+                };
+                let strength = match description.strength {
+                    Some(strength) => {
+                        self.number("strength", &strength, NOT_NEGATIVE, |x| x >= 0.0)?
+                    }
+                    None => 1.0,
+                };
+                let map =
+                    EnvironmentMap::load(&path).map_err(|err| SceneError::Image(path, err))?;
+                Ok(Environment::Image {
+                    map: std::sync::Arc::new(map),
+                    strength,
+                })
+            }
+            other => self.error(kind.span(), format!("Unknown environment type '{}'", other)),
+        }
+    }
+
+    fn fog(&self, description: FogDescription) -> Result<Fog, SceneError> {
+        let mut fog = FogBuilder::default();
+        if let Some(density) = description.density {
+            fog.density(self.number("density", &density, NOT_NEGATIVE, |x| x >= 0.0)?);
+        }
+        if let Some(height) = description.height {
+            fog.height(self.finite("height", &height)?);
+        }
+        if let Some(falloff) = description.falloff {
+            fog.falloff(self.finite("falloff", &falloff)?);
+        }
+        if let Some(color) = description.color {
+            fog.color(
+                self.number("color", &color, NOT_NEGATIVE, |x| x >= 0.0)?
+                    .into(),
+            );
+        }
+        Ok(fog.build().unwrap())
+    }
+
+    /// An image is decoded as sRGB if it is used as a color and as linear data if it is a
+    /// normal or bump map, which is what `srgb` says. If it is used both ways `srgb` is `None`
+    /// and the description has to decide.
+    fn texture(
+        &self,
+        description: TextureDescription,
+        srgb: Option<bool>,
+    ) -> Result<TextureKind, SceneError> {
+        let kind = &description.kind;
+        let given = description.properties();
+        match kind.get_ref().as_str() {
+            "solid" => {
+                self.allow(kind, &given, &["color"])?;
+                match description.color {
+                    Some(color) => Ok(TextureKind::Solid(self.finite("color", &color)?.into())),
+                    None => self.error(kind.span(), "Missing 'color' for solid"),
+                }
+            }
+            "checker" => {
+                self.allow(kind, &given, &["even", "odd", "scale"])?;
+                let mut builder = CheckerBuilder::default();
+                if let Some(even) = description.even {
+                    builder.even(Vector3::from(self.finite("even", &even)?));
+                }
+                if let Some(odd) = description.odd {
+                    builder.odd(Vector3::from(self.finite("odd", &odd)?));
+                }
+                if let Some(scale) = description.scale {
+                    builder.scale(self.number("scale", &scale, POSITIVE, |x| x > 0.0)?);
+                }
+                Ok(TextureKind::Checker(builder.build().unwrap()))
+            }
+            "uv_checker" => {
+                self.allow(kind, &given, &["even", "odd", "width", "height"])?;
+                let mut builder = UvCheckerBuilder::default();
+                if let Some(even) = description.even {
+                    builder.even(Vector3::from(self.finite("even", &even)?));
+                }
+                if let Some(odd) = description.odd {
+                    builder.odd(Vector3::from(self.finite("odd", &odd)?));
+                }
+                if let Some(width) = description.width {
+                    builder.width(width);
+                }
+                if let Some(height) = description.height {
+                    builder.height(height);
+                }
+                Ok(TextureKind::UvChecker(builder.build().unwrap()))
+            }
+            "image" => {
+                self.allow(kind, &given, &["path", "wrap", "filter", "srgb"])?;
+                let path = match description.path {
+                    Some(path) => self.path.with_file_name(path),
+                    None => return self.error(kind.span(), "Missing 'path' for image"),
+                };
+                let mut builder = ImageTextureBuilder::default();
+                if let Some(wrap) = description.wrap {
+                    builder.wrap(match wrap.get_ref().as_str() {
+                        "repeat" => WrapMode::Repeat,
+                        "clamp" => WrapMode::Clamp,
+                        "mirror" => WrapMode::Mirror,
+                        other => {
+                            return self
+                                .error(wrap.span(), format!("Unknown wrap mode '{}'", other))
+                        }
+                    });
+                }
+                if let Some(filter) = description.filter {
+                    builder.filter(match filter.get_ref().as_str() {
+                        "nearest" => Filter::Nearest,
+                        "bilinear" => Filter::Bilinear,
+                        other => {
+                            return self.error(filter.span(), format!("Unknown filter '{}'", other))
+                        }
+                    });
+                }
+                let srgb = match description.srgb.or(srgb) {
+                    Some(srgb) => srgb,
+                    None => {
+                        return self.error(
+                            kind.span(),
+                            "An image used as a color and as a normal or bump map needs 'srgb'",
+                        )
+                    }
+                };
+                let image = self
+                    .cache
+                    .borrow_mut()
+                    .load(&path, srgb)
+                    .map_err(|err| SceneError::Image(path, err))?;
+                Ok(TextureKind::Image(builder.image(image).build().unwrap()))
+            }
+            name @ ("perlin" | "fbm" | "turbulence" | "marble" | "wood") => {
+                self.allow(kind, &given, &["scale", "octaves", "seed", "low", "high"])?;
+                let mut builder = NoiseBuilder::default();
+                builder.pattern(match name {
+                    "perlin" => NoisePattern::Perlin,
+                    "fbm" => NoisePattern::Fbm,
+                    "turbulence" => NoisePattern::Turbulence,
+                    "marble" => NoisePattern::Marble,
+                    _ => NoisePattern::Wood,
+                });
+                if let Some(scale) = description.scale {
+                    builder.scale(self.number("scale", &scale, POSITIVE, |x| x > 0.0)?);
+                }
+                if let Some(octaves) = description.octaves {
+                    builder.octaves(octaves);
+                }
+                if let Some(seed) = description.seed {
+                    builder.seed(seed);
+                }
+                if let Some(low) = description.low {
+                    builder.low(self.finite("low", &low)?.into());
+                }
+                if let Some(high) = description.high {
+                    builder.high(self.finite("high", &high)?.into());
+                }
+                Ok(TextureKind::Noise(builder.build().unwrap()))
+            }
+            other => self.error(kind.span(), format!("Unknown texture type '{}'", other)),
+        }
+    }
+
+    /// The albedo of a material, either a constant color or a reference to a texture.
+    fn albedo(
+        &self,
+        description: &MaterialDescription,
+        textures: &HashMap<String, TextureKind>,
+    ) -> Result<Option<TextureKind>, SceneError> {
+        match (&description.albedo, &description.albedo_texture) {
+            (Some(_), Some(name)) => self.error(
+                name.span(),
+                "Only one of 'albedo' and 'albedo_texture' can be given",
+            ),
+            (Some(albedo), None) => Ok(Some(Vector3::from(self.finite("albedo", albedo)?).into())),
+            (None, Some(name)) => Ok(Some(self.texture_name(name, textures)?)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn normal_map(
+        &self,
+        description: &MaterialDescription,
+        textures: &HashMap<String, TextureKind>,
+    ) -> Result<Option<NormalMap>, SceneError> {
+        match (&description.normal_map, &description.bump_map) {
+            (Some(_), Some(name)) => self.error(
+                name.span(),
+                "Only one of 'normal_map' and 'bump_map' can be given",
+            ),
+            (Some(name), None) => {
+                if let Some(ref strength) = description.bump_strength {
+                    return self.error(strength.span(), "'bump_strength' requires a 'bump_map'");
+                }
+                Ok(Some(NormalMap::Tangent(self.texture_name(name, textures)?)))
+            }
+            (None, Some(name)) => Ok(Some(NormalMap::Bump {
+                height: self.texture_name(name, textures)?,
+                strength: match description.bump_strength {
+                    Some(ref strength) => self.finite("bump_strength", strength)?,
+                    None => 1.0,
+                },
+            })),
+            (None, None) => match description.bump_strength {
+                Some(ref strength) => {
+                    self.error(strength.span(), "'bump_strength' requires a 'bump_map'")
+                }
+                None => Ok(None),
+            },
+        }
+    }
+
+    fn thin_film(&self, description: &MaterialDescription) -> Result<Option<ThinFilm>, SceneError> {
+        match (&description.film_thickness, &description.film_ir) {
+            (Some(thickness), ir) => {
+                let thickness =
+                    self.number("film_thickness", thickness, NOT_NEGATIVE, |x| x >= 0.0)?;
+                let ir = match ir {
+                    Some(ir) => self.number("film_ir", ir, POSITIVE, |x| x > 0.0)?,
+                    None => 1.33,
+                };
+                Ok(Some(ThinFilm { thickness, ir }))
+            }
+            (None, Some(ir)) => self.error(ir.span(), "'film_ir' requires a 'film_thickness'"),
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn texture_name(
+        &self,
+        name: &Spanned<String>,
+        textures: &HashMap<String, TextureKind>,
+    ) -> Result<TextureKind, SceneError> {
+        match textures.get(name.get_ref()) {
+            Some(texture) => Ok(texture.clone()),
+            None => self.error(name.span(), format!("Unknown texture '{}'", name.get_ref())),
+        }
+    }
+
+    fn material(
+        &self,
+        description: MaterialDescription,
+        textures: &HashMap<String, TextureKind>,
+    ) -> Result<Material, SceneError> {
+        let kind = &description.kind;
+        let given = description.properties();
+        let unit = |x| (0.0..=1.0).contains(&x);
+        // Shared by all the non-emissive materials
+        let surface = [
+            "albedo",
+            "albedo_texture",
+            "normal_map",
+            "bump_map",
+            "bump_strength",
+        ];
+        match kind.get_ref().as_str() {
+            "diffuse" => {
+                self.allow(kind, &given, &surface)?;
+                let mut builder = DiffuseBuilder::default();
+                if let Some(albedo) = self.albedo(&description, textures)? {
+                    builder.albedo(albedo);
+                }
+                if let Some(normal_map) = self.normal_map(&description, textures)? {
+                    builder.normal_map(normal_map);
+                }
+                Ok(Material::Diffuse(builder.build().unwrap()))
+            }
+            "metal" => {
+                let metal = [
+                    "roughness",
+                    "anisotropy",
+                    "preset",
+                    "eta",
+                    "k",
+                    "film_thickness",
+                    "film_ir",
+                ];
+                self.allow(kind, &given, &[&surface[..], &metal].concat())?;
+                let mut builder = MetalBuilder::default();
+                if let Some(albedo) = self.albedo(&description, textures)? {
+                    builder.albedo(albedo);
+                }
+                if let Some(normal_map) = self.normal_map(&description, textures)? {
+                    builder.normal_map(normal_map);
+                }
+                if let Some(film) = self.thin_film(&description)? {
+                    builder.thin_film(film);
+                }
+                if let Some(ref roughness) = description.roughness {
+                    builder.roughness(self.number("roughness", roughness, UNIT, unit)?);
+                }
+                if let Some(ref anisotropy) = description.anisotropy {
+                    let valid = |x| (0.0..1.0).contains(&x);
+                    builder.anisotropy(self.number(
+                        "anisotropy",
+                        anisotropy,
+                        "in [0, 1)",
+                        valid,
+                    )?);
+                }
+                match (&description.preset, &description.eta, &description.k) {
+                    (Some(preset), None, None) => match Conductor::preset(preset.get_ref()) {
+                        Some(conductor) => {
+                            builder.conductor(conductor);
+                        }
+                        None => {
+                            return self.error(
+                                preset.span(),
+                                format!(
+                                    "Unknown preset '{}', expected one of {}",
+                                    preset.get_ref(),
+                                    Conductor::PRESETS.join(", ")
+                                ),
+                            )
+                        }
+                    },
+                    (Some(preset), _, _) => {
+                        return self.error(
+                            preset.span(),
+                            "Only one of 'preset' and 'eta'/'k' can be given",
+                        )
+                    }
+                    (None, Some(eta), Some(k)) => {
+                        builder.conductor(Conductor {
+                            eta: self.number("eta", eta, POSITIVE, |x| x > 0.0)?.into(),
+                            k: self.number("k", k, NOT_NEGATIVE, |x| x >= 0.0)?.into(),
+                        });
+                    }
+                    (None, None, None) => {}
+                    (None, Some(eta), None) => {
+                        return self.error(eta.span(), "'eta' and 'k' must be given together")
+                    }
+                    (None, None, Some(k)) => {
+                        return self.error(k.span(), "'eta' and 'k' must be given together")
+                    }
+                }
+                Ok(Material::Metal(builder.build().unwrap()))
+            }
+            "glass" => {
+                let glass = [
+                    "ir",
+                    "roughness",
+                    "absorption",
+                    "transmission_color",
+                    "transmission_distance",
+                    "dispersion",
+                    "cauchy",
+                    "sellmeier",
+                    "film_thickness",
+                    "film_ir",
+                ];
+                self.allow(kind, &given, &[&surface[..], &glass].concat())?;
+                let mut builder = GlassBuilder::default();
+                if let Some(albedo) = self.albedo(&description, textures)? {
+                    builder.albedo(albedo);
+                }
+                if let Some(normal_map) = self.normal_map(&description, textures)? {
+                    builder.normal_map(normal_map);
+                }
+                if let Some(film) = self.thin_film(&description)? {
+                    builder.thin_film(film);
+                }
+                if let Some(ref ir) = description.ir {
+                    builder.ir(self.number("ir", ir, POSITIVE, |x| x > 0.0)?);
+                }
+                if let Some(ref roughness) = description.roughness {
+                    builder.roughness(self.number("roughness", roughness, UNIT, unit)?);
+                }
+                match (
+                    &description.absorption,
+                    &description.transmission_color,
+                    &description.transmission_distance,
+                ) {
+                    (Some(absorption), None, None) => {
+                        let absorption =
+                            self.number("absorption", absorption, NOT_NEGATIVE, |x| x >= 0.0)?;
+                        builder.absorption(absorption.into());
+                    }
+                    (Some(absorption), _, _) => {
+                        return self.error(
+                            absorption.span(),
+                            "Only one of 'absorption' and 'transmission_color' can be given",
+                        )
+                    }
+                    (None, Some(color), distance) => {
+                        let color = self.number("transmission_color", color, UNIT, unit)?;
+                        let distance = match distance {
+                            Some(distance) => {
+                                self.number("transmission_distance", distance, POSITIVE, |x| {
+                                    x > 0.0
+                                })?
+                            }
+                            None => 1.0,
+                        };
+                        builder.transmission_color(color.into(), distance);
+                    }
+                    (None, None, Some(distance)) => {
+                        return self.error(
+                            distance.span(),
+                            "'transmission_distance' requires a 'transmission_color'",
+                        )
+                    }
+                    (None, None, None) => {}
+                }
+                let dispersion = match (
+                    &description.dispersion,
+                    &description.cauchy,
+                    &description.sellmeier,
+                ) {
+                    (Some(preset), None, None) => match Dispersion::preset(preset.get_ref()) {
+                        Some(dispersion) => Some(dispersion),
+                        None => {
+                            return self.error(
+                                preset.span(),
+                                format!(
+                                    "Unknown dispersion '{}', expected one of {}",
+                                    preset.get_ref(),
+                                    Dispersion::PRESETS.join(", ")
+                                ),
+                            )
+                        }
+                    },
+                    (None, Some(cauchy), None) => {
+                        let [a, b] = self.finite("cauchy", cauchy)?;
+                        Some(Dispersion::Cauchy { a, b })
+                    }
+                    (None, None, Some(sellmeier)) => {
+                        let [b, c] = self.finite("sellmeier", sellmeier)?;
+                        Some(Dispersion::Sellmeier { b, c })
+                    }
+                    (None, None, None) => None,
+                    _ => {
+                        return self.error(
+                            kind.span(),
+                            "Only one of 'dispersion', 'cauchy' and 'sellmeier' can be given",
+                        )
+                    }
+                };
+                if let Some(dispersion) = dispersion {
+                    if let Some(ref ir) = description.ir {
+                        return self
+                            .error(ir.span(), "'ir' can't be combined with a dispersion model");
+                    }
+                    if !dispersion.is_valid() {
+                        return self.error(
+                            kind.span(),
+                            "The dispersion model gives an invalid 'ir' between 380 and 780 nm",
+                        );
+                    }
+                    builder.dispersion(dispersion);
+                }
+                Ok(Material::Glass(builder.build().unwrap()))
+            }
+            "principled" => {
+                let principled = [
+                    "roughness",
+                    "anisotropy",
+                    "metallic",
+                    "specular",
+                    "transmission",
+                    "ir",
+                    "clearcoat",
+                    "clearcoat_roughness",
+                    "sheen",
+                    "sheen_tint",
+                ];
+                self.allow(kind, &given, &[&surface[..], &principled].concat())?;
+                let mut builder = PrincipledBuilder::default();
+                if let Some(albedo) = self.albedo(&description, textures)? {
+                    builder.base_color(albedo);
+                }
+                if let Some(normal_map) = self.normal_map(&description, textures)? {
+                    builder.normal_map(normal_map);
+                }
+                if let Some(ref roughness) = description.roughness {
+                    builder.roughness(self.number("roughness", roughness, UNIT, unit)?);
+                }
+                if let Some(ref metallic) = description.metallic {
+                    builder.metallic(self.number("metallic", metallic, UNIT, unit)?);
+                }
+                if let Some(ref specular) = description.specular {
+                    builder.specular(self.number("specular", specular, UNIT, unit)?);
+                }
+                if let Some(ref transmission) = description.transmission {
+                    builder.transmission(self.number("transmission", transmission, UNIT, unit)?);
+                }
+                if let Some(ref clearcoat) = description.clearcoat {
+                    builder.clearcoat(self.number("clearcoat", clearcoat, UNIT, unit)?);
+                }
+                if let Some(ref roughness) = description.clearcoat_roughness {
+                    builder.clearcoat_roughness(self.number(
+                        "clearcoat_roughness",
+                        roughness,
+                        UNIT,
+                        unit,
+                    )?);
+                }
+                if let Some(ref sheen) = description.sheen {
+                    builder.sheen(self.number("sheen", sheen, UNIT, unit)?);
+                }
+                if let Some(ref sheen_tint) = description.sheen_tint {
+                    builder.sheen_tint(self.number("sheen_tint", sheen_tint, UNIT, unit)?);
+                }
+                if let Some(ref anisotropy) = description.anisotropy {
+                    let valid = |x| (0.0..1.0).contains(&x);
+                    builder.anisotropy(self.number(
+                        "anisotropy",
+                        anisotropy,
+                        "in [0, 1)",
+                        valid,
+                    )?);
+                }
+                if let Some(ref ir) = description.ir {
+                    builder.ir(self.number("ir", ir, POSITIVE, |x| x > 0.0)?);
+                }
+                Ok(Material::Principled(builder.build().unwrap()))
+            }
+            "subsurface" => {
+                self.allow(
+                    kind,
+                    &given,
+                    &["albedo", "mean_free_path", "scattering", "absorption", "ir"],
+                )?;
+                let mut builder = SubsurfaceBuilder::default();
+                match (&description.mean_free_path, &description.scattering) {
+                    (Some(_), Some(scattering)) => {
+                        return self.error(
+                            scattering.span(),
+                            "Give either 'mean_free_path' or 'scattering', not both",
+                        )
+                    }
+                    (Some(mean_free_path), None) => {
+                        if let Some(ref absorption) = description.absorption {
+                            return self
+                                .error(absorption.span(), "'absorption' requires 'scattering'");
+                        }
+                        let mean_free_path =
+                            self.number("mean_free_path", mean_free_path, POSITIVE, |x| x > 0.0)?;
+                        let albedo = match description.albedo {
+                            Some(ref albedo) => self.number("albedo", albedo, UNIT, unit)?,
+                            None => [0.8, 0.8, 0.8],
+                        };
+                        builder.albedo(albedo.into(), mean_free_path.into());
+                    }
+                    (None, Some(scattering_span)) => {
+                        if let Some(ref albedo) = description.albedo {
+                            return self.error(albedo.span(), "'albedo' requires 'mean_free_path'");
+                        }
+                        let scattering =
+                            self.number("scattering", scattering_span, NOT_NEGATIVE, |x| x >= 0.0)?;
+                        let absorption = match description.absorption {
+                            Some(ref absorption) => {
+                                self.number("absorption", absorption, NOT_NEGATIVE, |x| x >= 0.0)?
+                            }
+                            None => [0.0, 0.0, 0.0],
+                        };
+                        if scattering
+                            .iter()
+                            .zip(&absorption)
+                            .any(|(s, a)| s + a <= 0.0)
+                        {
+                            return self.error(
+                                scattering_span.span(),
+                                "'scattering' plus 'absorption' must be positive",
+                            );
+                        }
+                        builder
+                            .scattering(scattering.into())
+                            .absorption(absorption.into());
+                    }
+                    (None, None) => {
+                        return self.error(
+                            kind.span(),
+                            "Missing 'mean_free_path' or 'scattering' for subsurface",
+                        )
+                    }
+                }
+                if let Some(ref ir) = description.ir {
+                    builder.ir(self.number("ir", ir, POSITIVE, |x| x > 0.0)?);
+                }
+                Ok(Material::Subsurface(builder.build().unwrap()))
+            }
+            "isotropic" => {
+                self.allow(kind, &given, &["albedo", "albedo_texture"])?;
+                let mut builder = IsotropicBuilder::default();
+                if let Some(albedo) = self.albedo(&description, textures)? {
+                    builder.albedo(albedo);
+                }
+                Ok(Material::Isotropic(builder.build().unwrap()))
+            }
+            "henyey_greenstein" => {
+                self.allow(kind, &given, &["albedo", "albedo_texture", "asymmetry"])?;
+                let mut builder = HenyeyGreensteinBuilder::default();
+                if let Some(albedo) = self.albedo(&description, textures)? {
+                    builder.albedo(albedo);
+                }
+                if let Some(ref asymmetry) = description.asymmetry {
+                    let valid = |x: f64| x.abs() < 1.0;
+                    builder.asymmetry(self.number("asymmetry", asymmetry, "in (-1, 1)", valid)?);
+                }
+                Ok(Material::HenyeyGreenstein(builder.build().unwrap()))
+            }
+            "emissive" => {
+                self.allow(kind, &given, &["color", "strength"])?;
+                let mut builder = EmissiveBuilder::default();
+                if let Some(ref color) = description.color {
+                    builder.color(
+                        self.number("color", color, NOT_NEGATIVE, |x| x >= 0.0)?
+                            .into(),
+                    );
+                }
+                if let Some(ref strength) = description.strength {
+                    builder
+                        .strength(self.number("strength", strength, NOT_NEGATIVE, |x| x >= 0.0)?);
+                }
+                Ok(Material::Emissive(builder.build().unwrap()))
+            }
+            other => self.error(kind.span(), format!("Unknown material type '{}'", other)),
+        }
+    }
+
+    fn material_name(
+        &self,
+        kind: &Spanned<String>,
+        material: Option<Spanned<String>>,
+        materials: &HashMap<String, Material>,
+    ) -> Result<String, SceneError> {
+        match material {
+            Some(name) if materials.contains_key(name.get_ref()) => Ok(name.into_inner()),
+            Some(name) => self.error(
+                name.span(),
+                format!("Unknown material '{}'", name.get_ref()),
+            ),
+            None => self.error(
+                kind.span(),
+                format!("Missing 'material' for {}", kind.get_ref()),
+            ),
+        }
+    }
+
+    fn object(
+        &self,
+        description: ObjectDescription,
+        materials: &HashMap<String, Material>,
+        textures: &HashMap<String, TextureKind>,
+    ) -> Result<SceneObject, SceneError> {
+        let kind = &description.kind;
+        let given = description.properties();
+        match kind.get_ref().as_str() {
+            "sphere" => {
+                self.allow(kind, &given, &["center", "radius"])?;
+                let (center, radius) = self.sphere(&description)?;
+                Ok(SceneObject::Sphere {
+                    center,
+                    radius,
+                    material: self.material_name(kind, description.material, materials)?,
+                })
+            }
+            "triangle" => {
+                self.allow(kind, &given, &["vertices"])?;
+                let vertices = match description.vertices {
+                    Some(ref vertices) => self.finite("vertices", vertices)?.map(Vector3::from),
+                    None => return self.error(kind.span(), "Missing 'vertices' for triangle"),
+                };
+                let material_span = description.material.as_ref().map(Spanned::span);
+                let material = self.material_name(kind, description.material, materials)?;
+                // A random walk has to reach the inside of the surface it entered again
+                if let (Material::Subsurface(_), Some(span)) =
+                    (&materials[&material], material_span)
+                {
+                    return self.error(span, "A triangle has no inside for a subsurface material");
+                }
+                Ok(SceneObject::Triangle { vertices, material })
+            }
+            "obj" => {
+                self.allow(kind, &given, &["path"])?;
+                let scene = self.obj(&description)?;
+                let material_span = description.material.as_ref().map(Spanned::span);
+                let material = match description.material {
+                    Some(_) => Some(self.material_name(kind, description.material, materials)?),
+                    None => None,
+                };
+                if let (Some(name), Some(span)) = (&material, material_span) {
+                    // Only the meshes without a material of their own get this one
+                    let meshes: Vec<_> = scene
+                        .meshes
+                        .iter()
+                        .filter(|mesh| match mesh.material {
+                            Some(ref name) => !scene.materials.contains_key(name),
+                            None => true,
+                        })
+                        .map(|mesh| &*mesh.data)
+                        .collect();
+                    if matches!(materials[name], Material::Subsurface(_))
+                        && !meshes.is_empty()
+                        && !is_closed(meshes)
+                    {
+                        return self
+                            .error(span, "An open OBJ has no inside for a subsurface material");
+                    }
+                }
+                Ok(SceneObject::Obj { scene, material })
+            }
+            "medium" => {
+                self.allow(kind, &given, &["density", "boundary"])?;
+                let density = match description.density {
+                    Some(ref density) => self.number("density", density, POSITIVE, |x| x > 0.0)?,
+                    None => 1.0,
+                };
+                let material = self.phase_function(kind, description.material, materials)?;
+                let boundary = match description.boundary {
+                    Some(boundary) => self.boundary(*boundary)?,
+                    None => return self.error(kind.span(), "Missing 'boundary' for medium"),
+                };
+                Ok(SceneObject::Medium {
+                    boundary,
+                    density,
+                    material,
+                })
+            }
+            "volume" => {
+                self.allow(
+                    kind,
+                    &given,
+                    &["density", "bounds", "grid", "resolution", "texture"],
+                )?;
+                let density = match description.density {
+                    Some(ref density) => self.number("density", density, POSITIVE, |x| x > 0.0)?,
+                    None => 1.0,
+                };
+                let bounds = match description.bounds {
+                    Some(ref bounds) => match self.finite("bounds", bounds)? {
+                        [minimum, maximum] if (0..3).all(|i| minimum[i] < maximum[i]) => {
+                            Aabb::new(minimum.into(), maximum.into())
+                        }
+                        _ => {
+                            return self.error(
+                                bounds.span(),
+                                "'bounds' must be a minimum and a larger maximum corner",
+                            )
+                        }
+                    },
+                    None => return self.error(kind.span(), "Missing 'bounds' for volume"),
+                };
+                let field = match (
+                    description.grid,
+                    description.resolution,
+                    description.texture,
+                ) {
+                    (Some(grid), Some(resolution), None) => {
+                        if resolution.get_ref().contains(&0) {
+                            return self.error(resolution.span(), "'resolution' must not be zero");
+                        }
+                        let path = self.path.with_file_name(grid);
+                        match DensityGrid::load_raw(&path, resolution.into_inner()) {
+                            Ok(grid) => DensityField::Grid(grid),
+                            Err(err) => return Err(SceneError::Io(path, err)),
+                        }
+                    }
+                    (None, None, Some(name)) => match self.texture_name(&name, textures)? {
+                        TextureKind::Noise(noise) => DensityField::Noise(noise),
+                        _ => return self.error(name.span(), "A volume texture must be noise"),
+                    },
+                    (Some(_), None, None) => {
+                        return self.error(kind.span(), "'grid' requires a 'resolution'")
+                    }
+                    _ => {
+                        return self.error(
+                            kind.span(),
+                            "A volume needs either a 'grid' and 'resolution' or a 'texture'",
+                        )
+                    }
+                };
+                Ok(SceneObject::Volume {
+                    bounds,
+                    field: Arc::new(field),
+                    density,
+                    material: self.phase_function(kind, description.material, materials)?,
+                })
+            }
+            other => self.error(kind.span(), format!("Unknown object type '{}'", other)),
+        }
+    }
+
+    /// The shape of a medium, which doesn't take a material of its own.
+    fn boundary(&self, description: ObjectDescription) -> Result<Boundary, SceneError> {
+        let kind = &description.kind;
+        let given = description.properties();
+        if let Some(ref material) = description.material {
+            return self.error(material.span(), "A boundary has no 'material'");
+        }
+        match kind.get_ref().as_str() {
+            "sphere" => {
+                self.allow(kind, &given, &["center", "radius"])?;
+                let (center, radius) = self.sphere(&description)?;
+                Ok(Boundary::Sphere { center, radius })
+            }
+            "obj" => {
+                self.allow(kind, &given, &["path"])?;
+                Ok(Boundary::Obj(self.obj(&description)?))
+            }
+            _ => self.error(kind.span(), "A boundary must be a closed 'sphere' or 'obj'"),
+        }
+    }
+
+    fn sphere(&self, description: &ObjectDescription) -> Result<(Vector3<f64>, f64), SceneError> {
+        let center = match description.center {
+            Some(ref center) => self.finite("center", center)?,
+            None => [0.0; 3],
+        };
+        let radius = match description.radius {
+            Some(ref radius) => self.number("radius", radius, POSITIVE, |x| x > 0.0)?,
+            None => 0.5,
+        };
+        Ok((center.into(), radius))
+    }
+
+    fn obj(&self, description: &ObjectDescription) -> Result<ObjScene, SceneError> {
+        let path = match description.path {
+            Some(ref path) => self.path.with_file_name(path),
+            None => return self.error(description.kind.span(), "Missing 'path' for obj"),
+        };
+        Ok(ObjScene::load_with_cache(
+            path,
+            &mut self.cache.borrow_mut(),
+        )?)
+    }
+
+    /// The name of the material of a medium, which must describe how it scatters.
+    fn phase_function(
+        &self,
+        kind: &Spanned<String>,
+        material: Option<Spanned<String>>,
+        materials: &HashMap<String, Material>,
+    ) -> Result<String, SceneError> {
+        let name = self.material_name(kind, material, materials)?;
+        match materials[&name] {
+            Material::Isotropic(_) | Material::HenyeyGreenstein(_) => Ok(name),
+            _ => self.error(
+                kind.span(),
+                "The material of a medium must be 'isotropic' or 'henyey_greenstein'",
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Scene, String> {
+        Scene::parse(source, Path::new("test.toml")).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn fog() {
+        let scene = parse("[fog]\ndensity = 0.2\ncolor = [0.5, 0.5, 0.5]").unwrap();
+        let fog = scene.fog.unwrap();
+        assert_eq!(fog.density, 0.2);
+        assert_eq!(fog.color, vector![0.5, 0.5, 0.5]);
+
+        for (table, error) in [
+            (
+                "density = -0.1",
+                "test.toml:3: 'density' must be finite and not negative",
+            ),
+            (
+                "density = inf",
+                "test.toml:3: 'density' must be finite and not negative",
+            ),
+            ("falloff = nan", "test.toml:3: 'falloff' must be finite"),
+            (
+                "color = [1.0, -1.0, 0.0]",
+                "test.toml:3: 'color' must be finite and not negative",
+            ),
+        ] {
+            let source = format!("\n[fog]\n{}", table);
+            assert_eq!(parse(&source).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn numbers_must_be_finite() {
+        let sphere = "[materials.white]\ntype = \"diffuse\"\n\n\
+                      [[objects]]\ntype = \"sphere\"\nmaterial = \"white\"\n";
+        for (property, error) in [
+            (
+                "radius = nan",
+                "test.toml:7: 'radius' must be finite and positive",
+            ),
+            (
+                "radius = inf",
+                "test.toml:7: 'radius' must be finite and positive",
+            ),
+            (
+                "radius = -1.0",
+                "test.toml:7: 'radius' must be finite and positive",
+            ),
+            (
+                "center = [0.0, -inf, 0.0]",
+                "test.toml:7: 'center' must be finite",
+            ),
+        ] {
+            let source = format!("{}{}", sphere, property);
+            assert_eq!(parse(&source).err().as_deref(), Some(error));
+        }
+
+        for (property, error) in [
+            ("ir = nan", "test.toml:3: 'ir' must be finite and positive"),
+            (
+                "roughness = nan",
+                "test.toml:3: 'roughness' must be between 0 and 1",
+            ),
+            (
+                "absorption = [0.0, inf, 0.0]",
+                "test.toml:3: 'absorption' must be finite and not negative",
+            ),
+        ] {
+            let source = format!("[materials.glass]\ntype = \"glass\"\n{}", property);
+            assert_eq!(parse(&source).err().as_deref(), Some(error));
+        }
+
+        let source = "[textures.checker]\ntype = \"checker\"\nscale = inf";
+        assert_eq!(
+            parse(source).err().as_deref(),
+            Some("test.toml:3: 'scale' must be finite and positive")
+        );
+        let source = "[materials.light]\ntype = \"emissive\"\nstrength = nan";
+        assert_eq!(
+            parse(source).err().as_deref(),
+            Some("test.toml:3: 'strength' must be finite and not negative")
+        );
+    }
+
+    #[test]
+    fn medium_boundary_is_only_a_shape() {
+        let smoke = "[materials.smoke]\ntype = \"isotropic\"\n\n\
+                     [[objects]]\ntype = \"medium\"\nmaterial = \"smoke\"\n";
+        let scene = parse(&format!(
+            "{}boundary = {{ type = \"sphere\", radius = 2.0 }}",
+            smoke
+        ));
+        let objects = scene.as_ref().unwrap().objects();
+        assert!(matches!(objects[..], [Object::Medium(_)]));
+
+        for (boundary, error) in [
+            (
+                "{ type = \"sphere\", material = \"smoke\" }",
+                "test.toml:7: A boundary has no 'material'",
+            ),
+            (
+                "{ type = \"triangle\" }",
+                "test.toml:7: A boundary must be a closed 'sphere' or 'obj'",
+            ),
+            (
+                "{ type = \"sphere\", radius = -1.0 }",
+                "test.toml:7: 'radius' must be finite and positive",
+            ),
+        ] {
+            let source = format!("{}boundary = {}", smoke, boundary);
+            assert_eq!(parse(&source).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn image_used_as_color_and_map_needs_srgb() {
+        let source = "[textures.bricks]\ntype = \"image\"\npath = \"bricks.png\"\n\n\
+                      [materials.wall]\ntype = \"diffuse\"\nalbedo_texture = \"bricks\"\n\n\
+                      [materials.bumpy]\ntype = \"diffuse\"\nbump_map = \"bricks\"";
+        assert_eq!(
+            parse(source).err().as_deref(),
+            Some("test.toml:2: An image used as a color and as a normal or bump map needs 'srgb'")
+        );
+        // With `srgb` given the image is loaded, which fails here
+        let source = source.replacen("\n\n", "\nsrgb = true\n\n", 1);
+        assert!(parse(&source).unwrap_err().contains("bricks.png"));
+    }
+
+    #[test]
+    fn subsurface_needs_an_inside() {
+        let wax = "[materials.wax]\ntype = \"subsurface\"\nmean_free_path = [1.0, 0.5, 0.2]\n";
+        let triangle = "[[objects]]\ntype = \"triangle\"\nmaterial = \"wax\"\n\
+                        vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]";
+        assert_eq!(
+            parse(&format!("{}\n{}", wax, triangle)).err().as_deref(),
+            Some("test.toml:7: A triangle has no inside for a subsurface material")
+        );
+        let sphere = "[[objects]]\ntype = \"sphere\"\nmaterial = \"wax\"";
+        assert!(parse(&format!("{}\n{}", wax, sphere)).is_ok());
+
+        // A tetrahedron, without its last face first
+        let path = std::env::temp_dir().join("rustyray-tetrahedron.obj");
+        let tetrahedron = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 1 3 2\nf 1 2 4\nf 1 4 3\n";
+        let obj = format!(
+            "[[objects]]\ntype = \"obj\"\nmaterial = \"wax\"\npath = \"{}\"",
+            path.display()
+        );
+        std::fs::write(&path, tetrahedron).unwrap();
+        let open = parse(&format!("{}\n{}", wax, obj));
+        std::fs::write(&path, format!("{}f 2 3 4\n", tetrahedron)).unwrap();
+        let closed = parse(&format!("{}\n{}", wax, obj));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            open.err().as_deref(),
+            Some("test.toml:7: An open OBJ has no inside for a subsurface material")
+        );
+        assert!(closed.is_ok());
+    }
+}
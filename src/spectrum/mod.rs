@@ -0,0 +1,269 @@
+use super::*;
+use std::ops::{Add, Mul};
+use std::sync::OnceLock;
+
+/// Visible range sampled for spectral effects, in nanometers.
+pub const WAVELENGTH_MIN: f64 = 380.0;
+pub const WAVELENGTH_MAX: f64 = 780.0;
+
+/// Number of wavelengths carried by each path in spectral mode.
+pub const HERO_WAVELENGTHS: usize = 4;
+
+/// The wavelengths a path carries in spectral mode: a uniformly sampled hero wavelength and
+/// the others evenly spaced after it, wrapping around the visible range.
+#[derive(Debug, Clone, Copy)]
+pub struct Wavelengths {
+    lambda: [f64; HERO_WAVELENGTHS],
+    /// Set once a wavelength dependent scattering event has dropped all but the hero
+    single: bool,
+}
+
+impl Wavelengths {
+    pub fn sample() -> Self {
+        let u = render_rng().gen::<f64>();
+        let mut lambda = [0.0; HERO_WAVELENGTHS];
+        for (i, wavelength) in lambda.iter_mut().enumerate() {
+            let t = (u + i as f64 / HERO_WAVELENGTHS as f64).fract();
+            *wavelength = WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+        }
+        Wavelengths {
+            lambda,
+            single: false,
+        }
+    }
+
+    /// A path that only carries `wavelength`.
+    pub fn single(wavelength: f64) -> Self {
+        Wavelengths {
+            lambda: [wavelength; HERO_WAVELENGTHS],
+            single: true,
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn is_single(&self) -> bool {
+        self.single
+    }
+
+    /// Drops every wavelength but the hero. The hero's contribution has to be scaled by
+    /// `HERO_WAVELENGTHS` to stay unbiased.
+    pub fn terminate_secondary(&self) -> Self {
+        Wavelengths::single(self.hero())
+    }
+
+    /// Converts radiance sampled at these wavelengths to linear RGB, scaled so that a constant
+    /// spectrum of 1 is white.
+    pub fn to_rgb(&self, radiance: &Vector4<f64>) -> Vector3<f64> {
+        static WHITE: OnceLock<Vector3<f64>> = OnceLock::new();
+        let white = WHITE.get_or_init(|| {
+            let steps = 4000;
+            let sum: Vector3<f64> = (0..steps)
+                .map(|i| {
+                    let t = (i as f64 + 0.5) / steps as f64;
+                    cie_xyz(WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN))
+                })
+                .sum();
+            xyz_to_rgb(&(sum / steps as f64))
+        });
+        let xyz: Vector3<f64> = self
+            .lambda
+            .iter()
+            .zip(radiance.iter())
+            .map(|(wavelength, value)| cie_xyz(*wavelength) * *value)
+            .sum();
+        xyz_to_rgb(&(xyz / HERO_WAVELENGTHS as f64)).component_div(white)
+    }
+}
+
+/// Smits' reflectance spectra for RGB upsampling, in ten bins from 380 to 720 nm.
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// The value at `wavelength` of a smooth spectrum with the given linear RGB color, after
+/// Smits 1999. Scales linearly with the color, so it also works for weights above 1.
+pub fn upsample(rgb: &Vector3<f64>, wavelength: f64) -> f64 {
+    let bin = (((wavelength - 380.0) / 34.0) as usize).min(9);
+    let (r, g, b) = (rgb[0], rgb[1], rgb[2]);
+    let white = SMITS_WHITE[bin];
+    if r <= g && r <= b {
+        r * white
+            + if g <= b {
+                (g - r) * SMITS_CYAN[bin] + (b - g) * SMITS_BLUE[bin]
+            } else {
+                (b - r) * SMITS_CYAN[bin] + (g - b) * SMITS_GREEN[bin]
+            }
+    } else if g <= r && g <= b {
+        g * white
+            + if r <= b {
+                (r - g) * SMITS_MAGENTA[bin] + (b - r) * SMITS_BLUE[bin]
+            } else {
+                (b - g) * SMITS_MAGENTA[bin] + (r - b) * SMITS_RED[bin]
+            }
+    } else {
+        b * white
+            + if r <= g {
+                (r - b) * SMITS_YELLOW[bin] + (g - r) * SMITS_GREEN[bin]
+            } else {
+                (g - b) * SMITS_YELLOW[bin] + (r - g) * SMITS_RED[bin]
+            }
+    }
+}
+
+/// Radiance carried along a path: RGB, or one value per wavelength in spectral mode.
+pub trait Radiance: Copy + Add<Output = Self> + Mul<f64, Output = Self> {
+    fn zero() -> Self;
+    /// Converts an RGB color or weight, upsampling it at the wavelengths of `ray` if needed.
+    fn from_rgb(rgb: &Vector3<f64>, ray: &Ray) -> Self;
+    fn component_mul(&self, other: &Self) -> Self;
+}
+
+impl Radiance for Vector3<f64> {
+    fn zero() -> Self {
+        Vector3::zeros()
+    }
+    fn from_rgb(rgb: &Vector3<f64>, _ray: &Ray) -> Self {
+        *rgb
+    }
+    fn component_mul(&self, other: &Self) -> Self {
+        self.component_mul(other)
+    }
+}
+
+impl Radiance for Vector4<f64> {
+    fn zero() -> Self {
+        Vector4::zeros()
+    }
+    fn from_rgb(rgb: &Vector3<f64>, ray: &Ray) -> Self {
+        let wavelengths = ray
+            .wavelengths
+            .expect("spectral radiance needs a ray with wavelengths");
+        let mut values = Vector4::from_fn(|i, _| upsample(rgb, wavelengths.lambda[i]));
+        if wavelengths.single {
+            values.rows_mut(1, HERO_WAVELENGTHS - 1).fill(0.0);
+        }
+        values
+    }
+    fn component_mul(&self, other: &Self) -> Self {
+        self.component_mul(other)
+    }
+}
+
+/// The CIE 1931 color matching functions, using the multi-lobe fit of Wyman et al. 2013.
+pub fn cie_xyz(wavelength: f64) -> Vector3<f64> {
+    let lobe = |mean: f64, sigma_low: f64, sigma_high: f64| {
+        let sigma = if wavelength < mean {
+            sigma_low
+        } else {
+            sigma_high
+        };
+        (-0.5 * ((wavelength - mean) / sigma).powi(2)).exp()
+    };
+    vector![
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+            - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8)
+    ]
+}
+
+/// CIE XYZ to linear sRGB with a D65 white point.
+pub fn xyz_to_rgb(xyz: &Vector3<f64>) -> Vector3<f64> {
+    matrix![
+        3.2406, -1.5372, -0.4986;
+        -0.9689, 1.8758, 0.0415;
+        0.0557, -0.2040, 1.0570
+    ] * xyz
+}
+
+/// The linear RGB a single wavelength contributes, scaled so that the average over uniformly
+/// sampled wavelengths is white. Out of gamut negatives are clipped.
+pub fn wavelength_rgb(wavelength: f64) -> Vector3<f64> {
+    static WHITE: OnceLock<Vector3<f64>> = OnceLock::new();
+    let white = WHITE.get_or_init(|| {
+        let steps = 4000;
+        let sum: Vector3<f64> = (0..steps)
+            .map(|i| {
+                let t = (i as f64 + 0.5) / steps as f64;
+                unnormalized_rgb(WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN))
+            })
+            .sum();
+        sum / steps as f64
+    });
+    unnormalized_rgb(wavelength).component_div(white)
+}
+
+fn unnormalized_rgb(wavelength: f64) -> Vector3<f64> {
+    xyz_to_rgb(&cie_xyz(wavelength)).map(|x| x.max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RGB of the spectrum upsampled from `rgb`, seen through the color matching functions.
+    fn round_trip(rgb: &Vector3<f64>) -> Vector3<f64> {
+        let steps = 1000;
+        let mut sum = Vector3::zeros();
+        for i in 0..steps {
+            let t = (i as f64 + 0.5) / steps as f64;
+            let wavelengths =
+                Wavelengths::single(WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN));
+            let radiance = Vector4::repeat(upsample(rgb, wavelengths.hero()));
+            sum += wavelengths.to_rgb(&radiance);
+        }
+        sum / steps as f64
+    }
+
+    #[test]
+    fn smits_round_trip() {
+        for rgb in [
+            vector![1.0, 1.0, 1.0],
+            vector![0.5, 0.5, 0.5],
+            vector![0.8, 0.2, 0.1],
+            vector![0.1, 0.6, 0.2],
+            vector![0.2, 0.3, 0.9],
+            vector![0.9, 0.8, 0.3],
+        ] {
+            let error = (round_trip(&rgb) - rgb).abs().max();
+            assert!(error < 0.01, "{} {}", rgb, error);
+        }
+    }
+
+    #[test]
+    fn wavelengths_are_evenly_spaced() {
+        seed_render_rng(5);
+        for _ in 0..100 {
+            let mut lambda = Wavelengths::sample().lambda;
+            assert!(lambda
+                .iter()
+                .all(|x| (WAVELENGTH_MIN..WAVELENGTH_MAX).contains(x)));
+            lambda.sort_by(f64::total_cmp);
+            let spacing = (WAVELENGTH_MAX - WAVELENGTH_MIN) / HERO_WAVELENGTHS as f64;
+            for pair in lambda.windows(2) {
+                assert!((pair[1] - pair[0] - spacing).abs() < 1e-9);
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use super::*;
+use image::error::{ParameterError, ParameterErrorKind};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Texels of an image in linear RGB, with the first row at the top.
+#[derive(Debug)]
+pub struct TextureImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vector3<f64>>,
+}
+
+impl TextureImage {
+    /// Load any format supported by the `image` features, `srgb` decodes the sRGB transfer
+    /// function for color data, it should be off for data like normal or height maps.
+    pub fn load(path: impl AsRef<Path>, srgb: bool) -> Result<Self, ImageError> {
+        let image = image::open(path)?.into_rgb8();
+        if image.width() == 0 || image.height() == 0 {
+            return Err(empty_image_error());
+        }
+        let decode: Vec<f64> = (0..=255u8)
+            .map(|x| {
+                let x = x as f64 / 255.0;
+                if srgb {
+                    srgb_to_linear(x)
+                } else {
+                    x
+                }
+            })
+            .collect();
+        let pixels = image
+            .pixels()
+            .map(|pixel| pixel.0.map(|x| decode[x as usize]).into())
+            .collect();
+        Ok(TextureImage {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels,
+        })
+    }
+
+    fn texel(&self, x: i64, y: i64, wrap: WrapMode) -> Vector3<f64> {
+        let x = wrap.apply(x, self.width);
+        let y = wrap.apply(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// The error for an image without pixels, which has nothing to sample.
+pub fn empty_image_error() -> ImageError {
+    let message = "the image has no pixels".to_string();
+    ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(
+        message,
+    )))
+}
+
+fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// How UVs outside of [0, 1] are mapped onto the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+/// An image mapped over the UV coordinates, (0, 0) is the bottom left corner.
+#[derive(Builder, Debug, Clone)]
+pub struct ImageTexture {
+    pub image: Arc<TextureImage>,
+    #[builder(default = "WrapMode::Repeat")]
+    pub wrap: WrapMode,
+    #[builder(default = "Filter::Bilinear")]
+    pub filter: Filter,
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, hit_rec: &HitRecord) -> Vector3<f64> {
+        let image = &self.image;
+        if image.pixels.is_empty() {
+            return vector![1.0, 0.0, 1.0];
+        }
+        let x = hit_rec.uv[0] * image.width as f64;
+        let y = (1.0 - hit_rec.uv[1]) * image.height as f64;
+        match self.filter {
+            Filter::Nearest => image.texel(x.floor() as i64, y.floor() as i64, self.wrap),
+            Filter::Bilinear => {
+                // Texel centers are at half-integer coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = (1.0 - fx) * image.texel(x0, y0, self.wrap)
+                    + fx * image.texel(x0 + 1, y0, self.wrap);
+                let bottom = (1.0 - fx) * image.texel(x0, y0 + 1, self.wrap)
+                    + fx * image.texel(x0 + 1, y0 + 1, self.wrap);
+                (1.0 - fy) * top + fy * bottom
+            }
+        }
+    }
+}
+
+/// Images loaded so far, so textures referencing the same file share one copy.
+#[derive(Debug, Default)]
+pub struct TextureCache {
+    images: HashMap<(PathBuf, bool), Arc<TextureImage>>,
+}
+
+impl TextureCache {
+    pub fn load(
+        &mut self,
+        path: impl AsRef<Path>,
+        srgb: bool,
+    ) -> Result<Arc<TextureImage>, ImageError> {
+        let path = path.as_ref();
+        let key = (
+            path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+            srgb,
+        );
+        if let Some(image) = self.images.get(&key) {
+            return Ok(image.clone());
+        }
+        let image = Arc::new(TextureImage::load(path, srgb)?);
+        self.images.insert(key, image.clone());
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(pixels: Vec<Vector3<f64>>, wrap: WrapMode, filter: Filter) -> ImageTexture {
+        let image = TextureImage {
+            width: pixels.len(),
+            height: 1,
+            pixels,
+        };
+        ImageTextureBuilder::default()
+            .image(Arc::new(image))
+            .wrap(wrap)
+            .filter(filter)
+            .build()
+            .unwrap()
+    }
+
+    fn value(texture: &ImageTexture, u: f64) -> f64 {
+        let material = Material::None;
+        let mut hit_rec = HitRecord::new(&material);
+        hit_rec.uv = vector![u, 0.5];
+        texture.value(&hit_rec)[0]
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let wrapped = |wrap: WrapMode| [-4, -3, -1, 0, 2, 3, 5, 6].map(|i| wrap.apply(i, 3));
+        assert_eq!(wrapped(WrapMode::Repeat), [2, 0, 2, 0, 2, 0, 2, 0]);
+        assert_eq!(wrapped(WrapMode::Clamp), [0, 0, 0, 0, 2, 2, 2, 2]);
+        assert_eq!(wrapped(WrapMode::Mirror), [2, 2, 0, 0, 2, 2, 0, 0]);
+    }
+
+    #[test]
+    fn filters_at_texel_edges() {
+        let pixels = vec![vector![0.0, 0.0, 0.0], vector![1.0, 1.0, 1.0]];
+
+        let nearest = texture(pixels.clone(), WrapMode::Repeat, Filter::Nearest);
+        assert_eq!(value(&nearest, 0.49), 0.0);
+        assert_eq!(value(&nearest, 0.51), 1.0);
+        assert_eq!(value(&nearest, 1.01), 0.0);
+
+        // Exact at texel centers, halfway in between
+        let repeat = texture(pixels.clone(), WrapMode::Repeat, Filter::Bilinear);
+        assert_eq!(value(&repeat, 0.25), 0.0);
+        assert_eq!(value(&repeat, 0.75), 1.0);
+        assert!((value(&repeat, 0.5) - 0.5).abs() < 1e-12);
+        assert!((value(&repeat, 0.375) - 0.25).abs() < 1e-12);
+        // The outer half texels blend with the other side only when repeating
+        assert!((value(&repeat, 0.0) - 0.5).abs() < 1e-12);
+        assert!((value(&repeat, 1.0) - 0.5).abs() < 1e-12);
+
+        let clamp = texture(pixels.clone(), WrapMode::Clamp, Filter::Bilinear);
+        assert_eq!(value(&clamp, 0.0), 0.0);
+        assert_eq!(value(&clamp, 1.0), 1.0);
+        assert_eq!(value(&clamp, -3.0), 0.0);
+
+        let mirror = texture(pixels, WrapMode::Mirror, Filter::Bilinear);
+        assert_eq!(value(&mirror, 0.0), 0.0);
+        assert_eq!(value(&mirror, 1.0), 1.0);
+        assert!((value(&mirror, 1.25) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn srgb_decoding() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-12);
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        // The linear and power segments meet
+        let knee = 0.04045;
+        assert!((srgb_to_linear(knee) - srgb_to_linear(knee + 1e-9)).abs() < 1e-6);
+
+        let path = std::env::temp_dir().join("rustyray-srgb.png");
+        image::save_buffer(&path, &[128, 0, 255], 1, 1, image::ColorType::Rgb8).unwrap();
+        let srgb = TextureImage::load(&path, true).unwrap();
+        let linear = TextureImage::load(&path, false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!((srgb.pixels[0][0] - srgb_to_linear(128.0 / 255.0)).abs() < 1e-12);
+        assert_eq!(linear.pixels[0], vector![128.0 / 255.0, 0.0, 1.0]);
+        assert_eq!(srgb.pixels[0][2], 1.0);
+    }
+}
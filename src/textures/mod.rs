@@ -0,0 +1,186 @@
+use super::*;
+
+mod image_texture;
+pub use image_texture::*;
+
+mod noise;
+pub use noise::*;
+
+#[derive(Debug, Clone)]
+pub enum TextureKind {
+    Solid(Vector3<f64>),
+    Checker(Checker),
+    UvChecker(UvChecker),
+    Image(ImageTexture),
+    Noise(Noise),
+}
+
+impl Texture for TextureKind {
+    fn value(&self, hit_rec: &HitRecord) -> Vector3<f64> {
+        match self {
+            TextureKind::Solid(color) => *color,
+            TextureKind::Checker(checker) => checker.value(hit_rec),
+            TextureKind::UvChecker(checker) => checker.value(hit_rec),
+            TextureKind::Image(image) => image.value(hit_rec),
+            TextureKind::Noise(noise) => noise.value(hit_rec),
+        }
+    }
+}
+
+impl From<Vector3<f64>> for TextureKind {
+    fn from(color: Vector3<f64>) -> Self {
+        TextureKind::Solid(color)
+    }
+}
+
+/// Alternating cubes of two textures in world space.
+#[derive(Builder, Debug, Clone)]
+pub struct Checker {
+    #[builder(setter(custom), default = "Box::new(vector![0.0, 0.0, 0.0].into())")]
+    pub even: Box<TextureKind>,
+    #[builder(setter(custom), default = "Box::new(vector![1.0, 1.0, 1.0].into())")]
+    pub odd: Box<TextureKind>,
+    /// Edge length of a cube
+    #[builder(default = "1.0")]
+    pub scale: f64,
+}
+
+impl CheckerBuilder {
+    pub fn even(&mut self, texture: impl Into<TextureKind>) -> &mut Self {
+        self.even = Some(Box::new(texture.into()));
+        self
+    }
+
+    pub fn odd(&mut self, texture: impl Into<TextureKind>) -> &mut Self {
+        self.odd = Some(Box::new(texture.into()));
+        self
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, hit_rec: &HitRecord) -> Vector3<f64> {
+        let parity: i64 = hit_rec
+            .point
+            .iter()
+            .map(|x| (x / self.scale).floor() as i64)
+            .sum();
+        if parity.rem_euclid(2) == 0 {
+            self.even.value(hit_rec)
+        } else {
+            self.odd.value(hit_rec)
+        }
+    }
+}
+
+/// Alternating squares of two textures in UV space.
+#[derive(Builder, Debug, Clone)]
+pub struct UvChecker {
+    #[builder(setter(custom), default = "Box::new(vector![0.0, 0.0, 0.0].into())")]
+    pub even: Box<TextureKind>,
+    #[builder(setter(custom), default = "Box::new(vector![1.0, 1.0, 1.0].into())")]
+    pub odd: Box<TextureKind>,
+    /// Number of squares along u
+    #[builder(default = "16")]
+    pub width: u32,
+    /// Number of squares along v
+    #[builder(default = "8")]
+    pub height: u32,
+}
+
+impl UvCheckerBuilder {
+    pub fn even(&mut self, texture: impl Into<TextureKind>) -> &mut Self {
+        self.even = Some(Box::new(texture.into()));
+        self
+    }
+
+    pub fn odd(&mut self, texture: impl Into<TextureKind>) -> &mut Self {
+        self.odd = Some(Box::new(texture.into()));
+        self
+    }
+}
+
+impl Texture for UvChecker {
+    fn value(&self, hit_rec: &HitRecord) -> Vector3<f64> {
+        let u = (hit_rec.uv[0] * self.width as f64).floor() as i64;
+        let v = (hit_rec.uv[1] * self.height as f64).floor() as i64;
+        if (u + v).rem_euclid(2) == 0 {
+            self.even.value(hit_rec)
+        } else {
+            self.odd.value(hit_rec)
+        }
+    }
+}
+
+impl<T: Texture> Texture for &T {
+    fn value(&self, hit_rec: &HitRecord) -> Vector3<f64> {
+        (*self).value(hit_rec)
+    }
+}
+
+pub trait Texture {
+    fn value(&self, hit_rec: &HitRecord) -> Vector3<f64>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Vector3<f64> = Vector3::new(0.0, 0.0, 0.0);
+    const WHITE: Vector3<f64> = Vector3::new(1.0, 1.0, 1.0);
+
+    fn at_point(point: Vector3<f64>) -> HitRecord<'static> {
+        let mut hit_rec = HitRecord::new(&Material::None);
+        hit_rec.point = point;
+        hit_rec
+    }
+
+    fn at_uv(u: f64, v: f64) -> HitRecord<'static> {
+        let mut hit_rec = HitRecord::new(&Material::None);
+        hit_rec.uv = vector![u, v];
+        hit_rec
+    }
+
+    #[test]
+    fn checker_alternates_across_zero() {
+        let checker = CheckerBuilder::default().scale(2.0).build().unwrap();
+        assert_eq!(checker.value(&at_point(vector![0.5, 0.5, 0.5])), BLACK);
+        assert_eq!(checker.value(&at_point(vector![2.5, 0.5, 0.5])), WHITE);
+        // Cubes keep their size on the negative side instead of mirroring around zero
+        assert_eq!(checker.value(&at_point(vector![-0.5, 0.5, 0.5])), WHITE);
+        assert_eq!(checker.value(&at_point(vector![-2.5, 0.5, 0.5])), BLACK);
+        assert_eq!(checker.value(&at_point(vector![-0.5, -0.5, 0.5])), BLACK);
+    }
+
+    #[test]
+    fn uv_checker_squares() {
+        let checker = UvCheckerBuilder::default()
+            .odd(vector![0.5, 0.5, 0.5])
+            .width(4)
+            .height(2)
+            .build()
+            .unwrap();
+        assert_eq!(checker.value(&at_uv(0.1, 0.1)), BLACK);
+        assert_eq!(checker.value(&at_uv(0.3, 0.1)), vector![0.5, 0.5, 0.5]);
+        assert_eq!(checker.value(&at_uv(0.3, 0.6)), BLACK);
+        assert_eq!(checker.value(&at_uv(0.9, 0.4)), vector![0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn nested_textures() {
+        let inner = UvCheckerBuilder::default()
+            .width(1)
+            .height(2)
+            .build()
+            .unwrap();
+        let checker = CheckerBuilder::default()
+            .even(TextureKind::UvChecker(inner))
+            .odd(vector![0.2, 0.3, 0.4])
+            .build()
+            .unwrap();
+        let mut hit_rec = at_uv(0.5, 0.75);
+        hit_rec.point = vector![0.5, 0.5, 0.5];
+        assert_eq!(checker.value(&hit_rec), WHITE);
+        hit_rec.point[0] = 1.5;
+        assert_eq!(checker.value(&hit_rec), vector![0.2, 0.3, 0.4]);
+    }
+}
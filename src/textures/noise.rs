@@ -0,0 +1,194 @@
+use super::*;
+use rand::seq::SliceRandom;
+use std::sync::Arc;
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise over a lattice of random unit vectors.
+#[derive(Debug)]
+pub struct Perlin {
+    gradients: Vec<Vector3<f64>>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = rngs::StdRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                Vector3::from_distribution(&Uniform::new(-1.0, 1.0), &mut rng)
+                    .try_normalize(1e-8)
+                    .unwrap_or_else(|| vector![1.0, 0.0, 0.0])
+            })
+            .collect();
+        let mut permutation = || {
+            let mut indices: Vec<usize> = (0..POINT_COUNT).collect();
+            indices.shuffle(&mut rng);
+            indices
+        };
+        let permutations = [permutation(), permutation(), permutation()];
+        Perlin {
+            gradients,
+            permutations,
+        }
+    }
+
+    /// Noise in roughly [-1, 1], zero at the lattice points.
+    pub fn noise(&self, point: &Vector3<f64>) -> f64 {
+        let floor = point.map(f64::floor);
+        let fraction = point - floor;
+        // Hermite smoothing hides the lattice
+        let smooth = fraction.map(|x| x * x * (3.0 - 2.0 * x));
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let corner = [di, dj, dk];
+                    let index = (0..3).fold(0, |hash, axis| {
+                        let lattice = floor[axis] as i64 + corner[axis] as i64;
+                        hash ^ self.permutations[axis][(lattice & 255) as usize]
+                    });
+                    let offset = fraction - Vector3::from(corner.map(|c| c as f64));
+                    let weight: f64 = (0..3)
+                        .map(|axis| {
+                            if corner[axis] == 1 {
+                                smooth[axis]
+                            } else {
+                                1.0 - smooth[axis]
+                            }
+                        })
+                        .product();
+                    accum += weight * self.gradients[index].dot(&offset);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Fractal Brownian motion, octaves of noise with doubling frequency and halving amplitude.
+    pub fn fbm(&self, point: &Vector3<f64>, octaves: u32) -> f64 {
+        self.octaves(point, octaves, |x| x)
+    }
+
+    /// Like `fbm` but summing the absolute values, which gives sharp creases.
+    pub fn turbulence(&self, point: &Vector3<f64>, octaves: u32) -> f64 {
+        self.octaves(point, octaves, f64::abs)
+    }
+
+    fn octaves(&self, point: &Vector3<f64>, octaves: u32, map: impl Fn(f64) -> f64) -> f64 {
+        let mut accum = 0.0;
+        let mut point = *point;
+        let mut amplitude = 1.0;
+        for _ in 0..octaves {
+            accum += amplitude * map(self.noise(&point));
+            amplitude *= 0.5;
+            point *= 2.0;
+        }
+        accum
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoisePattern {
+    Perlin,
+    Fbm,
+    Turbulence,
+    Marble,
+    Wood,
+}
+
+/// A procedural texture blending between two colors with a noise pattern in world space.
+#[derive(Builder, Debug, Clone)]
+#[builder(build_fn(skip))]
+pub struct Noise {
+    pub pattern: NoisePattern,
+    /// Frequency of the pattern in world space
+    pub scale: f64,
+    pub octaves: u32,
+    seed: u64,
+    pub low: Vector3<f64>,
+    pub high: Vector3<f64>,
+
+    #[builder(setter(skip))]
+    perlin: Arc<Perlin>,
+}
+
+impl Noise {
+    /// The seed of the noise lattice, fixed at build time.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Where the pattern at `position` lies between `low` at 0 and `high` at 1.
+    pub fn blend(&self, position: &Vector3<f64>) -> f64 {
+        let point = self.scale * position;
+        let perlin = &self.perlin;
+        let t = match self.pattern {
+            NoisePattern::Perlin => 0.5 * (1.0 + perlin.noise(&point)),
+            NoisePattern::Fbm => 0.5 * (1.0 + perlin.fbm(&point, self.octaves)),
+            NoisePattern::Turbulence => perlin.turbulence(&point, self.octaves),
+            // Sine stripes along z, distorted by lower frequency turbulence
+            NoisePattern::Marble => {
+                let turbulence = perlin.turbulence(position, self.octaves);
+                0.5 * (1.0 + (point[2] + 10.0 * turbulence).sin())
+            }
+            // Concentric rings around the y axis with noisy radii
+            NoisePattern::Wood => {
+                let radius = point[0].hypot(point[2]) + 0.3 * perlin.fbm(&point, self.octaves);
+                radius.rem_euclid(1.0)
+            }
+        };
+        t.clamp(0.0, 1.0)
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, hit_rec: &HitRecord) -> Vector3<f64> {
+        let t = self.blend(&hit_rec.point);
+        (1.0 - t) * self.low + t * self.high
+    }
+}
+
+impl NoiseBuilder {
+    pub fn build(&self) -> Result<Noise, NoiseBuilderError> {
+        let seed = self.seed.unwrap_or(0);
+        Ok(Noise {
+            pattern: self.pattern.unwrap_or(NoisePattern::Perlin),
+            scale: self.scale.unwrap_or(1.0),
+            octaves: self.octaves.unwrap_or(7),
+            seed,
+            low: self.low.unwrap_or_else(Vector3::zeros),
+            high: self.high.unwrap_or_else(|| vector![1.0, 1.0, 1.0]),
+            perlin: Arc::new(Perlin::new(seed)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wood_rings_wrap_near_the_axis() {
+        let wood = NoiseBuilder::default()
+            .pattern(NoisePattern::Wood)
+            .build()
+            .unwrap();
+        let mut rng = rngs::StdRng::seed_from_u64(1);
+        // The noise pushes radii this close to the axis below 0
+        let blends: Vec<_> = (0..1000)
+            .map(|_| {
+                let position = vector![
+                    rng.gen_range(-0.1..0.1),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-0.1..0.1)
+                ];
+                wood.blend(&position)
+            })
+            .collect();
+        assert!(blends.iter().all(|t| (0.0..=1.0).contains(t)));
+        assert!(blends.iter().filter(|t| **t > 0.5).count() > 100);
+        assert!(blends.iter().filter(|t| **t == 0.0).count() < 10);
+    }
+}
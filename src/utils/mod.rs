@@ -1,7 +1,40 @@
 use super::*;
+use std::cell::RefCell;
+
+thread_local! {
+    static RENDER_RNG: RefCell<rngs::StdRng> = RefCell::new(rngs::StdRng::from_entropy());
+}
+
+/// Handle to the per-thread generator used while rendering, see `seed_render_rng`.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderRng;
+
+impl RngCore for RenderRng {
+    fn next_u32(&mut self) -> u32 {
+        RENDER_RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+    fn next_u64(&mut self) -> u64 {
+        RENDER_RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RENDER_RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        RENDER_RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+pub fn render_rng() -> RenderRng {
+    RenderRng
+}
+
+/// Reseed the current thread's render generator, for reproducible renders.
+pub fn seed_render_rng(seed: u64) {
+    RENDER_RNG.with(|rng| *rng.borrow_mut() = rngs::StdRng::seed_from_u64(seed));
+}
 
 pub fn random_in_unit_sphere() -> Vector3<f64> {
-    let mut rng = thread_rng();
+    let mut rng = render_rng();
     loop {
         let array = Vector3::from_distribution(&Uniform::new(-1.0, 1.0), &mut rng);
         if array.dot(&array) < 1.0 {
@@ -11,7 +44,7 @@ pub fn random_in_unit_sphere() -> Vector3<f64> {
 }
 
 pub fn random_in_unit_disk() -> Vector3<f64> {
-    let mut rng = thread_rng();
+    let mut rng = render_rng();
     let between = Uniform::new(-1.0, 1.0);
     loop {
         let array = vector![between.sample(&mut rng), between.sample(&mut rng), 0.0];